@@ -0,0 +1,22 @@
+//! Render a chart to an svg string without touching the filesystem.
+//!
+//! This only needs the `svg` feature, so it also works when compiled to WebAssembly:
+//!
+//! ```sh
+//! cargo build --example svg_string --target wasm32-wasi --no-default-features --features svg
+//! ```
+use mermaid::Pie;
+
+fn main() {
+    let chart = Pie::parse(
+        r#"
+pie title Pets adopted by volunteers
+    "Dogs" : 386
+    "Cats" : 85
+    "Rats" : 15
+    "#,
+    )
+    .unwrap();
+    let svg = chart.to_svg_string(None).unwrap();
+    println!("{}", svg);
+}
@@ -0,0 +1,74 @@
+//! Cheap estimates of how expensive a chart will be to render.
+//!
+//! These are computed from the parsed chart without doing any layout, so they can be used to
+//! reject or queue large charts before spending time rendering them.
+
+/// A rough measure of the size of a chart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Complexity {
+    /// The number of nodes in the chart (for a pie chart, the number of data points).
+    pub nodes: usize,
+    /// The number of edges in the chart (always 0 for a pie chart).
+    pub edges: usize,
+    /// A unitless estimate of the rendering cost.
+    ///
+    /// This is only useful for comparing charts against each other (or against some threshold).
+    /// It grows roughly in line with the amount of work layout has to do, so a layered layout
+    /// (O(V + E)) scores `nodes + edges`.
+    pub cost: usize,
+}
+
+impl Complexity {
+    /// Estimate the complexity of a chart drawn using a layered layout.
+    pub(crate) fn layered(nodes: usize, edges: usize) -> Self {
+        Self {
+            nodes,
+            edges,
+            cost: nodes + edges,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Flowchart, Pie};
+
+    #[test]
+    fn flowchart() {
+        let chart =
+            Flowchart::parse("flowchart LR\n  A --> B & C\n  B --> D\n  C --> D\n  E").unwrap();
+        assert_eq!(
+            chart.complexity(),
+            Complexity {
+                nodes: 5,
+                edges: 4,
+                cost: 9,
+            }
+        );
+
+        let chart = Flowchart::parse("flowchart TD").unwrap();
+        assert_eq!(chart.complexity(), Complexity::layered(0, 0));
+    }
+
+    #[test]
+    fn pie() {
+        let chart =
+            Pie::parse("pie title Pets\n  \"Dogs\": 386\n  \"Cats\": 85\n  \"Rats\": 15").unwrap();
+        assert_eq!(
+            chart.complexity(),
+            Complexity {
+                nodes: 3,
+                edges: 0,
+                cost: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn bigger_charts_cost_more() {
+        let small = Flowchart::parse("flowchart LR\nA --> B").unwrap();
+        let big = Flowchart::parse("flowchart LR\nA --> B --> C --> D\nA --> D").unwrap();
+        assert!(big.complexity().cost > small.complexity().cost);
+    }
+}
@@ -0,0 +1,143 @@
+use super::{Connector, Flowchart, Node};
+
+/// The differences between two flowcharts, as returned by [`Flowchart::diff`].
+///
+/// Nodes are matched by id and edges by the ids of the nodes they connect. All lists are sorted
+/// by id.
+#[derive(Debug, Default)]
+pub struct FlowchartDiff<'input> {
+    /// Nodes that are only in the new chart.
+    pub added_nodes: Vec<Node<'input>>,
+    /// Nodes that are only in the old chart.
+    pub removed_nodes: Vec<Node<'input>>,
+    /// Nodes that are in both charts, but have a different label or shape (old, new).
+    pub changed_nodes: Vec<(Node<'input>, Node<'input>)>,
+    /// Edges that are only in the new chart (from, to, connector).
+    pub added_edges: Vec<(&'input str, &'input str, Connector<'input>)>,
+    /// Edges that are only in the old chart (from, to, connector).
+    pub removed_edges: Vec<(&'input str, &'input str, Connector<'input>)>,
+    /// Edges that are in both charts, but look different (from, to, old, new).
+    pub changed_edges: Vec<(
+        &'input str,
+        &'input str,
+        Connector<'input>,
+        Connector<'input>,
+    )>,
+}
+
+impl FlowchartDiff<'_> {
+    /// Whether the two charts were the same.
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+            && self.changed_edges.is_empty()
+    }
+}
+
+impl<'input> Flowchart<'input> {
+    /// Find the nodes and edges that differ between `self` (the old chart) and `other` (the new
+    /// chart).
+    ///
+    /// The chart direction is not compared.
+    pub fn diff(&self, other: &Flowchart<'input>) -> FlowchartDiff<'input> {
+        let mut diff = FlowchartDiff::default();
+
+        for (id, old) in &self.nodes {
+            match other.nodes.get(id) {
+                Some(new) if new != old => diff.changed_nodes.push((*old, *new)),
+                Some(_) => (),
+                None => diff.removed_nodes.push(*old),
+            }
+        }
+        for (id, new) in &other.nodes {
+            if !self.nodes.contains_key(id) {
+                diff.added_nodes.push(*new);
+            }
+        }
+
+        for (from, to, old) in self.graph.all_edges() {
+            match other.graph.edge_weight(from, to) {
+                Some(new) if new != old => diff.changed_edges.push((from, to, *old, *new)),
+                Some(_) => (),
+                None => diff.removed_edges.push((from, to, *old)),
+            }
+        }
+        for (from, to, new) in other.graph.all_edges() {
+            if !self.graph.contains_edge(from, to) {
+                diff.added_edges.push((from, to, *new));
+            }
+        }
+
+        diff.added_nodes.sort_by_key(|node| node.id);
+        diff.removed_nodes.sort_by_key(|node| node.id);
+        diff.changed_nodes.sort_by_key(|(node, _)| node.id);
+        diff.added_edges.sort_by_key(|&(from, to, _)| (from, to));
+        diff.removed_edges.sort_by_key(|&(from, to, _)| (from, to));
+        diff.changed_edges
+            .sort_by_key(|&(from, to, _, _)| (from, to));
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flowchart::LineStyle;
+
+    #[test]
+    fn same_chart() {
+        let old = Flowchart::parse("flowchart LR\nA[Start] --> B").unwrap();
+        let new = Flowchart::parse("flowchart TD\n  A[Start]-->B").unwrap();
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn changed_label() {
+        let old = Flowchart::parse("flowchart LR\nA[Start] --> B").unwrap();
+        let new = Flowchart::parse("flowchart LR\nA[Begin] --> B").unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_nodes.len(), 1);
+        let (before, after) = diff.changed_nodes[0];
+        assert_eq!((before.label, after.label), ("Start", "Begin"));
+        assert!(diff.added_nodes.is_empty() && diff.removed_nodes.is_empty());
+        assert!(diff.added_edges.is_empty() && diff.changed_edges.is_empty());
+    }
+
+    #[test]
+    fn added_edge() {
+        let old = Flowchart::parse("flowchart LR\nA --> B\nC").unwrap();
+        let new = Flowchart::parse("flowchart LR\nA --> B\nB -.-> C").unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_edges.len(), 1);
+        let (from, to, conn) = diff.added_edges[0];
+        assert_eq!((from, to, conn.line_style), ("B", "C", LineStyle::Dotted));
+        assert!(diff.added_nodes.is_empty() && diff.changed_nodes.is_empty());
+    }
+
+    #[test]
+    fn removed_node() {
+        let old = Flowchart::parse("flowchart LR\nA --> B --> C").unwrap();
+        let new = Flowchart::parse("flowchart LR\nA --> B").unwrap();
+        let diff = old.diff(&new);
+        let removed = diff.removed_nodes.iter().map(|node| node.id);
+        assert_eq!(removed.collect::<Vec<_>>(), ["C"]);
+        // its edges go with it
+        let removed = diff.removed_edges.iter().map(|&(from, to, _)| (from, to));
+        assert_eq!(removed.collect::<Vec<_>>(), [("B", "C")]);
+        assert!(diff.added_nodes.is_empty() && diff.added_edges.is_empty());
+    }
+
+    #[test]
+    fn changed_edge() {
+        let old = Flowchart::parse("flowchart LR\nA --> B").unwrap();
+        let new = Flowchart::parse("flowchart LR\nA ==>|yes| B").unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_edges.len(), 1);
+        let (_, _, before, after) = diff.changed_edges[0];
+        assert_eq!(before.line_style, LineStyle::Normal);
+        assert_eq!((after.line_style, after.label), (LineStyle::Thick, "yes"));
+    }
+}
@@ -0,0 +1,269 @@
+use super::{
+    ArrowStyle, Connector, EdgeInlineStyle, Error, Flowchart, LineStyle, Node, NodeInlineStyle,
+    NodeStyle,
+};
+use crate::{
+    style::color_to_hex,
+    text::{escape_quotes, QUOT_ENTITY},
+};
+use std::{collections::HashSet, fmt::Write};
+
+/// Characters that mean a node label has to be quoted.
+const SPECIAL_CHARS: &[char] = &[
+    '"', '[', ']', '(', ')', '{', '}', '<', '>', '/', '\\', '|', '&',
+];
+
+impl<'input> Flowchart<'input> {
+    /// Write this flowchart back out as mermaid source.
+    ///
+    /// The output is in a canonical form: one connection per line, indented by 4 spaces, with
+    /// each node's label and shape given the first time the node appears. Parsing the output
+    /// gives back an equivalent flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "flowchart {}", self.direction).unwrap();
+
+        // nodes only need defining the first time they are used
+        let mut defined = HashSet::new();
+        for (from, to, conn) in self.graph.all_edges() {
+            out.push_str("    ");
+            self.write_node(&mut out, from, &mut defined);
+            out.push(' ');
+            write_connector(&mut out, conn);
+            out.push(' ');
+            self.write_node(&mut out, to, &mut defined);
+            out.push('\n');
+        }
+
+        // nodes without any connections, in a stable order
+        let mut unconnected = self
+            .nodes
+            .keys()
+            .filter(|id| !defined.contains(*id))
+            .copied()
+            .collect::<Vec<_>>();
+        unconnected.sort_unstable();
+        for id in unconnected {
+            out.push_str("    ");
+            self.write_node(&mut out, id, &mut defined);
+            out.push('\n');
+        }
+
+        let mut styled = self
+            .nodes
+            .values()
+            .filter_map(|node| Some((node.id, node.inline_style?)))
+            .collect::<Vec<_>>();
+        styled.sort_unstable_by_key(|(id, _)| *id);
+        for (id, style) in styled {
+            write_inline_style(&mut out, id, &style);
+        }
+        for (idx, (_, _, conn)) in self.graph.all_edges().enumerate() {
+            if let Some(ref style) = conn.inline_style {
+                write_link_style(&mut out, idx, style);
+            }
+        }
+        out
+    }
+
+    /// Parse some flowchart source, and write it back out in the canonical form (see
+    /// [`Flowchart::to_mermaid`]).
+    ///
+    /// Formatting source that is already in the canonical form doesn't change it.
+    pub fn format(input: &str) -> Result<String, Error> {
+        Ok(Flowchart::parse(input)?.to_mermaid())
+    }
+
+    fn write_node(&self, out: &mut String, id: &'input str, defined: &mut HashSet<&'input str>) {
+        out.push_str(id);
+        if !defined.insert(id) {
+            return;
+        }
+        match self.nodes.get(id) {
+            Some(node) if !node.is_id() || node.style != NodeStyle::Square => {
+                write_shape(out, node)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Write the label and shape of a node (everything after the ID).
+fn write_shape(out: &mut String, node: &Node) {
+    let (start, end) = match node.style {
+        NodeStyle::Square => ("[", "]"),
+        NodeStyle::Round => ("(", ")"),
+        NodeStyle::Stadium => ("([", "])"),
+        NodeStyle::Subroutine => ("[[", "]]"),
+        NodeStyle::Cylinder => ("[(", ")]"),
+        NodeStyle::Circle => ("((", "))"),
+        NodeStyle::Asymmetric => (">", "]"),
+        NodeStyle::Rhombus => ("{", "}"),
+        NodeStyle::Hexagon => ("{{", "}}"),
+        NodeStyle::Parallelogram => ("[/", "/]"),
+        NodeStyle::ParallelogramRev => ("[\\", "\\]"),
+        NodeStyle::Trapezoid => ("[/", "\\]"),
+        NodeStyle::TrapezoidRev => ("[\\", "/]"),
+        NodeStyle::DoubleCircle => ("(((", ")))"),
+    };
+    // there has to be some label for the shape to parse, so fall back to the id
+    let label = node.label_or_id();
+    out.push_str(start);
+    if label.contains(SPECIAL_CHARS) || label.contains(QUOT_ENTITY) || label.trim() != label {
+        write!(out, "\"{}\"", escape_quotes(label)).unwrap();
+    } else {
+        out.push_str(label);
+    }
+    out.push_str(end);
+}
+
+/// Write a `style` statement for a node.
+fn write_inline_style(out: &mut String, id: &str, style: &NodeInlineStyle) {
+    let mut decls = vec![];
+    if let Some(fill) = style.fill {
+        decls.push(format!("fill:{}", color_to_hex(fill)));
+    }
+    if let Some(stroke) = style.stroke {
+        decls.push(format!("stroke:{}", color_to_hex(stroke)));
+    }
+    if let Some(width) = style.stroke_width {
+        decls.push(format!("stroke-width:{}px", width));
+    }
+    if let Some(color) = style.color {
+        decls.push(format!("color:{}", color_to_hex(color)));
+    }
+    // a statement with nothing in it wouldn't parse
+    if !decls.is_empty() {
+        writeln!(out, "    style {} {}", id, decls.join(",")).unwrap();
+    }
+}
+
+/// Write a `linkStyle` statement for the edge with index `idx`.
+fn write_link_style(out: &mut String, idx: usize, style: &EdgeInlineStyle) {
+    let mut decls = vec![];
+    if let Some(stroke) = style.stroke {
+        decls.push(format!("stroke:{}", color_to_hex(stroke)));
+    }
+    if let Some(width) = style.stroke_width {
+        decls.push(format!("stroke-width:{}px", width));
+    }
+    if let Some([dash, gap]) = style.dash {
+        decls.push(format!("stroke-dasharray:{} {}", dash, gap));
+    }
+    if !decls.is_empty() {
+        writeln!(out, "    linkStyle {} {}", idx, decls.join(",")).unwrap();
+    }
+}
+
+/// Write a connector, including its label.
+fn write_connector(out: &mut String, conn: &Connector) {
+    if let Some(arrow) = conn.arrow_start {
+        out.push(match arrow {
+            ArrowStyle::Arrow => '<',
+            ArrowStyle::Circle => 'o',
+            ArrowStyle::Cross => 'x',
+        });
+    }
+    if conn.line_style == LineStyle::Dotted {
+        out.push('-');
+        for _ in 0..conn.rank.max(1) {
+            out.push('.');
+        }
+        out.push('-');
+    } else {
+        let line = if conn.line_style == LineStyle::Thick {
+            '='
+        } else {
+            '-'
+        };
+        // the parser always takes one line segment off the rank, and another if there's no
+        // arrow at the end
+        let len = usize::from(conn.rank) + 1 + usize::from(conn.arrow_end.is_none());
+        for _ in 0..len {
+            out.push(line);
+        }
+    }
+    if let Some(arrow) = conn.arrow_end {
+        out.push(match arrow {
+            ArrowStyle::Arrow => '>',
+            ArrowStyle::Circle => 'o',
+            ArrowStyle::Cross => 'x',
+        });
+    }
+    if !conn.label.is_empty() {
+        let label = conn.label;
+        if label.contains(['|', '"']) || label.contains(QUOT_ENTITY) || label.trim() != label {
+            write!(out, "|\"{}\"|", escape_quotes(label)).unwrap();
+        } else {
+            write!(out, "|{}|", label).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        diagrams::flowchart::{Direction, NodeStyle},
+        text::unescape,
+    };
+
+    /// Check that formatting `src` and then formatting the output again doesn't change it, and
+    /// return the formatted source.
+    fn format_twice(src: &str) -> String {
+        let formatted = Flowchart::format(src).unwrap();
+        assert_eq!(Flowchart::format(&formatted).unwrap(), formatted);
+        formatted
+    }
+
+    #[test]
+    fn messy_input() {
+        let src = "graph LR\n  A[Start]-->B{Is it?}  \n\n B -->| yes |C((ok))\nB-.->D\n";
+        assert_eq!(
+            format_twice(src),
+            "flowchart LR\n    A[Start] --> B{Is it?}\n    B -->|yes| C((ok))\n    B -.-> D\n"
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        for src in [
+            "flowchart TD\n    A\n",
+            "flowchart BT\n    A[\"a [bracketed] label\"] ==> B\n",
+            "flowchart RL\n    A -->|\"a | b\"| B\n    B --- C\n    C <--> A\n",
+            "flowchart TD\n    A(\"say \\\"hi\\\"\") --> B[\"say #quot;hi#quot;\"]\n",
+            "flowchart TD\n    A --> B\n    style A fill:#ff0000,stroke-width:4px\n",
+            "flowchart TD\n    A --> B\n    B --> C\n    linkStyle 1 stroke:#0000ff\n",
+        ] {
+            let formatted = format_twice(src);
+            let first = Flowchart::parse(src).unwrap();
+            let second = Flowchart::parse(&formatted).unwrap();
+            assert_eq!(first.nodes, second.nodes);
+            assert_eq!(first.graph.edge_count(), second.graph.edge_count());
+        }
+    }
+
+    #[test]
+    fn quotes_in_labels() {
+        let mut chart = Flowchart::new(Direction::TopBottom);
+        let node = Node {
+            id: "A",
+            label: "say \"hi\"",
+            style: NodeStyle::Round,
+            inline_style: None,
+        };
+        chart.add_node(&node).unwrap();
+        let conn = Connector::new(LineStyle::Normal).with_label("\"quoted\"");
+        chart.add_edge("A", "B", conn).unwrap();
+
+        let src = chart.to_mermaid();
+        let parsed = Flowchart::parse(&src).unwrap();
+        assert_eq!(
+            unescape(parsed.nodes["A"].label),
+            unescape(chart.nodes["A"].label)
+        );
+        let (_, _, conn) = parsed.graph.all_edges().next().unwrap();
+        assert_eq!(unescape(conn.label), "\"quoted\"");
+        assert_eq!(format_twice(&src), src);
+    }
+}
@@ -0,0 +1,661 @@
+//! A layered (Sugiyama style) layout for flowcharts.
+//!
+//! The layout happens in 4 steps:
+//!
+//!  1. Each node is given a rank (the layer it goes in), using the longest path from the sources
+//!     of the graph. Edges that would close a cycle are reversed while ranking.
+//!  2. Edges that span more than one layer get a virtual node in each layer they pass through, so
+//!     that every edge only connects adjacent layers.
+//!  3. The nodes in each layer are ordered to reduce crossings, using the median heuristic.
+//!  4. The layers are given coordinates, in the direction the flowchart flows.
+//!
+//! Edges that were reversed (back edges) and self loops are routed as curves, so they stand out
+//! from the rest of the flow.
+//!
+//! Parts of the chart that aren't connected to each other are laid out separately, and then
+//! placed side by side across the flow.
+use super::{Direction, Flowchart, Node};
+use kurbo::{BezPath, Point, Rect, Size, Vec2};
+use petgraph::unionfind::UnionFind;
+use std::collections::HashMap;
+
+/// The space between adjacent layers.
+const RANK_SPACING: f64 = 50.;
+/// The space between adjacent nodes in the same layer.
+const NODE_SPACING: f64 = 50.;
+/// How many times to sweep up and down the layers when ordering nodes.
+const ORDER_SWEEPS: usize = 4;
+/// How far a self loop sticks out from the side of its node.
+const SELF_LOOP_SIZE: f64 = 20.;
+/// How far a back edge between adjacent layers bends away from a straight line, so it doesn't
+/// sit on top of the edge going the other way.
+const BACK_EDGE_BEND: f64 = 20.;
+
+/// Where the nodes and edges of a flowchart should be drawn.
+#[derive(Debug, Clone)]
+pub struct FlowchartLayout<'input> {
+    /// The size of the area containing the whole flowchart (the top left is at the origin).
+    pub size: Size,
+    /// Where each node goes, keyed by node id.
+    pub nodes: HashMap<&'input str, NodeLayout>,
+    /// The route each edge takes, in the same order as `Flowchart::graph.all_edges()`.
+    pub edges: Vec<EdgeLayout<'input>>,
+}
+
+/// The position of a node in a [`FlowchartLayout`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NodeLayout {
+    /// The area the node takes up.
+    pub rect: Rect,
+    /// The layer the node is in, counting from the start of the flow (`0` is the first layer).
+    pub rank: usize,
+}
+
+/// The route of an edge in a [`FlowchartLayout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeLayout<'input> {
+    /// The id of the node the edge starts at.
+    pub from: &'input str,
+    /// The id of the node the edge ends at.
+    pub to: &'input str,
+    /// The points the edge passes through, from the center of `from` to the center of `to`.
+    ///
+    /// Use [`boundary_point`](super::boundary_point) to find where the edge meets the node
+    /// outlines. Self loops are the exception: they start and end on the side of the node.
+    pub points: Vec<Point>,
+    /// Whether the edge should be drawn as a curve through `points` rather than straight lines
+    /// (see [`EdgeLayout::path`]).
+    ///
+    /// This is set for self loops and for edges that go against the flow, closing a cycle.
+    pub curved: bool,
+}
+
+impl EdgeLayout<'_> {
+    /// Get the path to draw for the edge.
+    pub fn path(&self) -> BezPath {
+        let mut path = BezPath::new();
+        let points = &self.points[..];
+        path.move_to(points[0]);
+        if !self.curved {
+            for &point in &points[1..] {
+                path.line_to(point);
+            }
+            return path;
+        }
+        match points.len() {
+            2 => {
+                // bend to the side, so the edge doesn't cover one going the other way
+                let (start, end) = (points[0], points[1]);
+                let along = end - start;
+                let side = Vec2::new(-along.y, along.x).normalize() * BACK_EDGE_BEND;
+                if side.is_finite() {
+                    path.quad_to(start.midpoint(end) + side, end);
+                } else {
+                    path.line_to(end);
+                }
+            }
+            len => {
+                // a smooth curve using the points as control points, through their midpoints
+                for idx in 1..len - 2 {
+                    path.quad_to(points[idx], points[idx].midpoint(points[idx + 1]));
+                }
+                path.quad_to(points[len - 2], points[len - 1]);
+            }
+        }
+        path
+    }
+}
+
+impl<'input> Flowchart<'input> {
+    /// Work out where to draw each node and edge.
+    ///
+    /// `node_size` gives the size of each node, including its outline. Nodes are arranged in
+    /// layers following `direction`, which is usually [`Flowchart::direction`], and connections
+    /// with a higher [`rank`](super::Connector::rank) span more layers.
+    pub fn layout(
+        &self,
+        direction: Direction,
+        mut node_size: impl FnMut(&Node<'input>) -> Size,
+    ) -> FlowchartLayout<'input> {
+        // sort the ids so the layout doesn't depend on hashmap order
+        let mut ids = self.nodes.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| (*id, idx))
+            .collect::<HashMap<_, _>>();
+        let edges = self
+            .graph
+            .all_edges()
+            .map(|(from, to, conn)| (index[from], index[to], usize::from(conn.rank.max(1))))
+            .collect::<Vec<_>>();
+
+        let reversed = back_edges(ids.len(), &edges);
+        let ranks = rank(ids.len(), &edges, &reversed);
+
+        let horizontal = matches!(direction, Direction::LeftRight | Direction::RightLeft);
+        // with `width` along the flow and `height` across it, like `Vertex::size`
+        let sizes = ids
+            .iter()
+            .map(|id| {
+                let size = node_size(&self.nodes[id]);
+                if horizontal {
+                    size
+                } else {
+                    Size::new(size.height, size.width)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Each connected component is laid out on its own, and then they are put side by side
+        // across the flow, so that parts of the chart that aren't connected don't get mixed up.
+        let mut centers = vec![Point::ZERO; ids.len()];
+        // the points each edge passes through, in the direction of the edge
+        let mut chains = vec![vec![]; edges.len()];
+        let (mut main_len, mut cross_len) = (0., 0.);
+        for (component_idx, component) in components(ids.len(), &edges).into_iter().enumerate() {
+            let mut layered = Layered::default();
+            let vertex = component
+                .iter()
+                .map(|&node| (node, layered.add_vertex(ranks[node], sizes[node])))
+                .collect::<HashMap<_, _>>();
+            // the chain of vertices each edge in the component passes through
+            let mut vertex_chains = vec![];
+            for (idx, (&(from, to, _), &reversed)) in edges.iter().zip(&reversed).enumerate() {
+                if !vertex.contains_key(&from) {
+                    continue;
+                }
+                if from == to {
+                    // self loops don't take part in the layering
+                    vertex_chains.push((idx, vec![vertex[&from], vertex[&to]]));
+                    continue;
+                }
+                let (start, end) = if reversed { (to, from) } else { (from, to) };
+                let mut chain = vec![vertex[&start]];
+                for rank in ranks[start] + 1..ranks[end] {
+                    let dummy = layered.add_vertex(rank, Size::ZERO);
+                    layered.add_segment(*chain.last().unwrap(), dummy);
+                    chain.push(dummy);
+                }
+                layered.add_segment(*chain.last().unwrap(), vertex[&end]);
+                chain.push(vertex[&end]);
+                if reversed {
+                    chain.reverse();
+                }
+                vertex_chains.push((idx, chain));
+            }
+
+            layered.order();
+            let (vertex_centers, main, cross) = layered.coordinates();
+            let offset = if component_idx == 0 {
+                Vec2::ZERO
+            } else {
+                Vec2::new(0., cross_len + NODE_SPACING)
+            };
+            for &node in &component {
+                centers[node] = vertex_centers[vertex[&node]] + offset;
+            }
+            for (idx, chain) in vertex_chains {
+                chains[idx] = chain
+                    .into_iter()
+                    .map(|v| vertex_centers[v] + offset)
+                    .collect();
+            }
+            main_len = f64::max(main_len, main);
+            cross_len = offset.y + cross;
+        }
+
+        // convert from (along the flow, across the flow) to (x, y)
+        let to_point = |pos: Point| match direction {
+            Direction::TopBottom => Point::new(pos.y, pos.x),
+            Direction::BottomTop => Point::new(pos.y, main_len - pos.x),
+            Direction::LeftRight => pos,
+            Direction::RightLeft => Point::new(main_len - pos.x, pos.y),
+        };
+        let mut size = if horizontal {
+            Size::new(main_len, cross_len)
+        } else {
+            Size::new(cross_len, main_len)
+        };
+        let nodes: HashMap<_, _> = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| {
+                let size = if horizontal {
+                    sizes[idx]
+                } else {
+                    Size::new(sizes[idx].height, sizes[idx].width)
+                };
+                let rect = Rect::from_center_size(to_point(centers[idx]), size);
+                let rank = ranks[idx];
+                (*id, NodeLayout { rect, rank })
+            })
+            .collect();
+        let edges = self
+            .graph
+            .all_edges()
+            .zip(chains)
+            .zip(reversed)
+            .map(|(((from, to, _), chain), reversed)| {
+                if from == to {
+                    let points = self_loop(nodes[from].rect, horizontal);
+                    // the loop sticks out across the flow, so might need more space
+                    let far = points[1];
+                    size = Size::new(size.width.max(far.x), size.height.max(far.y));
+                    return EdgeLayout {
+                        from,
+                        to,
+                        points,
+                        curved: true,
+                    };
+                }
+                EdgeLayout {
+                    from,
+                    to,
+                    points: chain.into_iter().map(to_point).collect(),
+                    curved: reversed,
+                }
+            })
+            .collect();
+        FlowchartLayout { size, nodes, edges }
+    }
+}
+
+/// The points a self loop passes through, on the side of a node across the flow.
+fn self_loop(rect: Rect, horizontal: bool) -> Vec<Point> {
+    let center = rect.center();
+    if horizontal {
+        // below the node
+        let offset = rect.width() * 0.25;
+        let (x0, x1) = (center.x - offset, center.x + offset);
+        let y = rect.y1 + SELF_LOOP_SIZE;
+        vec![
+            Point::new(x0, rect.y1),
+            Point::new(x0, y),
+            Point::new(x1, y),
+            Point::new(x1, rect.y1),
+        ]
+    } else {
+        // to the right of the node
+        let offset = rect.height() * 0.25;
+        let (y0, y1) = (center.y - offset, center.y + offset);
+        let x = rect.x1 + SELF_LOOP_SIZE;
+        vec![
+            Point::new(rect.x1, y0),
+            Point::new(x, y0),
+            Point::new(x, y1),
+            Point::new(rect.x1, y1),
+        ]
+    }
+}
+
+/// Split the nodes into groups that are connected to each other by edges.
+///
+/// The groups are in order of their first node, and the nodes in each group are in order.
+fn components(node_count: usize, edges: &[(usize, usize, usize)]) -> Vec<Vec<usize>> {
+    let mut sets = UnionFind::new(node_count);
+    for &(from, to, _) in edges {
+        sets.union(from, to);
+    }
+    let mut components: Vec<Vec<usize>> = vec![];
+    // the index in `components` for the representative of each set
+    let mut component_idx = HashMap::new();
+    for node in 0..node_count {
+        let idx = *component_idx.entry(sets.find(node)).or_insert_with(|| {
+            components.push(vec![]);
+            components.len() - 1
+        });
+        components[idx].push(node);
+    }
+    components
+}
+
+/// Find edges that close a cycle, using a depth first search.
+///
+/// Reversing these edges leaves a graph without any cycles. Self loops are always included.
+fn back_edges(node_count: usize, edges: &[(usize, usize, usize)]) -> Vec<bool> {
+    let mut outgoing = vec![vec![]; node_count];
+    for (idx, &(from, to, _)) in edges.iter().enumerate() {
+        outgoing[from].push((to, idx));
+    }
+
+    #[derive(Copy, Clone, PartialEq)]
+    enum State {
+        Unvisited,
+        OnStack,
+        Done,
+    }
+    let mut state = vec![State::Unvisited; node_count];
+    let mut back = vec![false; edges.len()];
+    for root in 0..node_count {
+        if state[root] != State::Unvisited {
+            continue;
+        }
+        // (node, index of the next outgoing edge to follow)
+        let mut stack = vec![(root, 0)];
+        state[root] = State::OnStack;
+        while let Some(&mut (node, ref mut next)) = stack.last_mut() {
+            match outgoing[node].get(*next) {
+                Some(&(to, edge)) => {
+                    *next += 1;
+                    match state[to] {
+                        State::Unvisited => {
+                            state[to] = State::OnStack;
+                            stack.push((to, 0));
+                        }
+                        State::OnStack => back[edge] = true,
+                        State::Done => (),
+                    }
+                }
+                None => {
+                    state[node] = State::Done;
+                    stack.pop();
+                }
+            }
+        }
+    }
+    back
+}
+
+/// Give each node a rank, so every edge goes to a higher rank, at least as far as the edge's
+/// length.
+///
+/// Edges marked in `reversed` are treated as going the other way.
+fn rank(node_count: usize, edges: &[(usize, usize, usize)], reversed: &[bool]) -> Vec<usize> {
+    let mut outgoing = vec![vec![]; node_count];
+    let mut incoming_count = vec![0; node_count];
+    for (&(from, to, len), &reversed) in edges.iter().zip(reversed) {
+        if from == to {
+            // self loops don't affect the ranking
+            continue;
+        }
+        let (from, to) = if reversed { (to, from) } else { (from, to) };
+        outgoing[from].push((to, len));
+        incoming_count[to] += 1;
+    }
+
+    // visit the nodes in topological order, pushing each node's successors down past it
+    let mut ranks = vec![0; node_count];
+    let mut ready = (0..node_count)
+        .filter(|&node| incoming_count[node] == 0)
+        .collect::<Vec<_>>();
+    while let Some(node) = ready.pop() {
+        for &(to, len) in &outgoing[node] {
+            ranks[to] = ranks[to].max(ranks[node] + len);
+            incoming_count[to] -= 1;
+            if incoming_count[to] == 0 {
+                ready.push(to);
+            }
+        }
+    }
+    ranks
+}
+
+/// A vertex in the layered graph: either a node or a point an edge passes through.
+#[derive(Debug)]
+struct Vertex {
+    /// The size of the vertex, with `width` along the flow and `height` across it.
+    size: Size,
+    /// Vertices in the previous layer with a segment to this one.
+    above: Vec<usize>,
+    /// Vertices in the next layer this one has a segment to.
+    below: Vec<usize>,
+}
+
+/// A graph where every segment joins vertices in adjacent layers.
+#[derive(Debug, Default)]
+struct Layered {
+    vertices: Vec<Vertex>,
+    /// The vertices in each layer, in order across the flow.
+    layers: Vec<Vec<usize>>,
+}
+
+impl Layered {
+    fn add_vertex(&mut self, rank: usize, size: Size) -> usize {
+        let idx = self.vertices.len();
+        self.vertices.push(Vertex {
+            size,
+            above: vec![],
+            below: vec![],
+        });
+        if self.layers.len() <= rank {
+            self.layers.resize_with(rank + 1, Vec::new);
+        }
+        self.layers[rank].push(idx);
+        idx
+    }
+
+    fn add_segment(&mut self, from: usize, to: usize) {
+        self.vertices[from].below.push(to);
+        self.vertices[to].above.push(from);
+    }
+
+    /// Order the vertices in each layer to reduce the number of crossing segments.
+    ///
+    /// Each sweep moves vertices to the median position of their neighbors in the layer before.
+    /// The best order found is kept.
+    fn order(&mut self) {
+        let mut best = self.layers.clone();
+        let mut best_crossings = self.crossings();
+        for sweep in 0..ORDER_SWEEPS {
+            let down = sweep % 2 == 0;
+            for step in 1..self.layers.len() {
+                let layer = if down {
+                    step
+                } else {
+                    self.layers.len() - 1 - step
+                };
+                let fixed = if down { layer - 1 } else { layer + 1 };
+                let position = self.positions(fixed);
+                let vertices = &self.vertices;
+                let mut keyed = self.layers[layer]
+                    .iter()
+                    .enumerate()
+                    .map(|(current, &v)| {
+                        let neighbors = if down {
+                            &vertices[v].above
+                        } else {
+                            &vertices[v].below
+                        };
+                        let mut neighbors =
+                            neighbors.iter().map(|n| position[n]).collect::<Vec<_>>();
+                        neighbors.sort_unstable_by(f64::total_cmp);
+                        // vertices without any neighbors stay where they are
+                        let key = match neighbors.len() {
+                            0 => current as f64,
+                            len if len % 2 == 1 => neighbors[len / 2],
+                            len => (neighbors[len / 2 - 1] + neighbors[len / 2]) * 0.5,
+                        };
+                        (key, v)
+                    })
+                    .collect::<Vec<_>>();
+                // a stable sort, so ties keep their current order
+                keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+                self.layers[layer] = keyed.into_iter().map(|(_, v)| v).collect();
+            }
+            let crossings = self.crossings();
+            if crossings < best_crossings {
+                best = self.layers.clone();
+                best_crossings = crossings;
+            }
+        }
+        self.layers = best;
+    }
+
+    /// The position of each vertex in `layer`, keyed by vertex.
+    fn positions(&self, layer: usize) -> HashMap<usize, f64> {
+        self.layers[layer]
+            .iter()
+            .enumerate()
+            .map(|(pos, &v)| (v, pos as f64))
+            .collect()
+    }
+
+    /// Count the number of pairs of segments that cross.
+    fn crossings(&self) -> usize {
+        let mut count = 0;
+        for layer in 1..self.layers.len() {
+            let above = self.positions(layer - 1);
+            let below = self.positions(layer);
+            let segments = self.layers[layer]
+                .iter()
+                .flat_map(|&v| {
+                    let below = below[&v];
+                    self.vertices[v].above.iter().map(move |u| (u, below))
+                })
+                .map(|(u, below)| (above[u], below))
+                .collect::<Vec<_>>();
+            for (idx, a) in segments.iter().enumerate() {
+                for b in &segments[idx + 1..] {
+                    if (a.0 - b.0) * (a.1 - b.1) < 0. {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Get the center of each vertex, with `x` along the flow and `y` across it, along with the
+    /// total length of the layout along and across the flow.
+    ///
+    /// Each layer is centered across the flow.
+    fn coordinates(&self) -> (Vec<Point>, f64, f64) {
+        let layer_len = |layer: &Vec<usize>| {
+            let sizes = layer.iter().map(|&v| self.vertices[v].size.height);
+            let gaps = layer.len().saturating_sub(1) as f64 * NODE_SPACING;
+            sizes.sum::<f64>() + gaps
+        };
+        let cross_len = self.layers.iter().map(layer_len).fold(0., f64::max);
+
+        let mut centers = vec![Point::ZERO; self.vertices.len()];
+        let mut main = 0.;
+        for (idx, layer) in self.layers.iter().enumerate() {
+            if idx > 0 {
+                main += RANK_SPACING;
+            }
+            let thickness = layer
+                .iter()
+                .map(|&v| self.vertices[v].size.width)
+                .fold(0., f64::max);
+            let mut cross = (cross_len - layer_len(layer)) * 0.5;
+            for &v in layer {
+                let size = self.vertices[v].size;
+                centers[v] = Point::new(main + thickness * 0.5, cross + size.height * 0.5);
+                cross += size.height + NODE_SPACING;
+            }
+            main += thickness;
+        }
+        (centers, main, cross_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagrams::flowchart::{Connector, LineStyle};
+
+    /// Lay out a flowchart, with every node the same size.
+    fn layout(src: &str) -> FlowchartLayout<'_> {
+        let chart = Flowchart::parse(src).unwrap();
+        chart.layout(chart.direction, |_| Size::new(40., 20.))
+    }
+
+    fn assert_finite(layout: &FlowchartLayout) {
+        assert!(layout.size.is_finite(), "{:?}", layout.size);
+        for node in layout.nodes.values() {
+            assert!(node.rect.is_finite(), "{:?}", node);
+        }
+        for edge in &layout.edges {
+            assert!(edge.points.iter().all(|p| p.is_finite()), "{:?}", edge);
+            assert!(edge.path().elements().len() > 1);
+        }
+    }
+
+    #[test]
+    fn self_loop() {
+        let layout = layout("flowchart TD\nA-->A");
+        assert_finite(&layout);
+        assert!(layout.edges[0].curved);
+        // the loop has to fit on the canvas
+        let far = layout.edges[0].points[1];
+        assert!(far.x <= layout.size.width && far.y <= layout.size.height);
+    }
+
+    #[test]
+    fn cycle() {
+        let layout = layout("flowchart TD\nA-->B\nB-->C\nC-->A");
+        assert_finite(&layout);
+        let mut ranks = layout.nodes.values().map(|n| n.rank).collect::<Vec<_>>();
+        ranks.sort_unstable();
+        assert_eq!(ranks, [0, 1, 2]);
+        // exactly one edge goes against the flow
+        assert_eq!(layout.edges.iter().filter(|e| e.curved).count(), 1);
+
+        let layout = self::layout("flowchart LR\nA-->B\nB-->A");
+        assert_finite(&layout);
+    }
+
+    #[test]
+    fn disconnected_components() {
+        for direction in ["TD", "LR"] {
+            let src = format!("flowchart {}\nA-->B\nB-->C\nD-->E\nE-->F", direction);
+            let layout = layout(&src);
+            let bounds = |ids: &[&str]| {
+                ids.iter()
+                    .map(|id| layout.nodes[id].rect)
+                    .reduce(|a, b| a.union(b))
+                    .unwrap()
+            };
+            let first = bounds(&["A", "B", "C"]);
+            let second = bounds(&["D", "E", "F"]);
+            assert!(
+                first.intersect(second).area() == 0.,
+                "{} {:?} {:?}",
+                direction,
+                first,
+                second
+            );
+            // both fit on the canvas
+            let canvas = Rect::from_origin_size(Point::ZERO, layout.size);
+            assert_eq!(canvas.union(first).union(second), canvas);
+        }
+    }
+
+    #[test]
+    fn directions() {
+        let center = |direction: &str, id: &str| {
+            let src = format!("flowchart {}\nA-->B", direction);
+            layout(&src).nodes[id].rect.center()
+        };
+        // sources go at the start of the flow, whichever way it goes
+        assert!(center("TD", "A").y < center("TD", "B").y);
+        assert!(center("BT", "A").y > center("BT", "B").y);
+        assert!(center("LR", "A").x < center("LR", "B").x);
+        assert!(center("RL", "A").x > center("RL", "B").x);
+        // and the nodes line up across the flow
+        assert_eq!(center("BT", "A").x, center("BT", "B").x);
+        assert_eq!(center("RL", "A").y, center("RL", "B").y);
+    }
+
+    #[test]
+    fn longer_links_span_more_layers() {
+        let span = |src: &str| {
+            let layout = layout(src);
+            layout.nodes["B"].rank - layout.nodes["A"].rank
+        };
+        assert_eq!(span("flowchart TD\nA-->B"), 1);
+        assert!(span("flowchart TD\nA---->B") > span("flowchart TD\nA-->B"));
+
+        let mut chart = Flowchart::new(Direction::TopBottom);
+        let conn = Connector::new(LineStyle::Normal).with_rank(3);
+        chart.add_edge("A", "B", conn).unwrap();
+        let layout = chart.layout(chart.direction, |_| Size::new(40., 20.));
+        let (a, b) = (layout.nodes["A"], layout.nodes["B"]);
+        assert!(b.rank >= a.rank + 3);
+        // there's room for the layers in between
+        assert!(b.rect.y0 - a.rect.y1 >= 3. * RANK_SPACING);
+        assert_eq!(layout.edges[0].points.len(), 4);
+    }
+}
@@ -1,14 +1,60 @@
-// TODO error handling - loads of places currently panic where they should error gracefully
-
+mod diff;
+mod format;
+#[cfg(feature = "render")]
+mod layout;
+mod owned;
 mod parse;
-use anyhow::{anyhow, Result};
+mod query;
+#[cfg(feature = "render")]
+mod render;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod validate;
+#[cfg(feature = "svg")]
+use super::{render_error, svg};
+#[cfg(feature = "render")]
+use crate::style::{LineCap, StrokeStyle, TextMeasurer, TextStyle};
+use crate::{
+    complexity::Complexity,
+    style::Color,
+    text::{unescape, RichText},
+};
+#[cfg(feature = "render")]
+use kurbo::Size;
+#[cfg(feature = "render")]
+use once_cell::sync::Lazy;
 use petgraph::graphmap::GraphMap;
+#[cfg(feature = "render")]
+use piet::RenderContext;
+#[cfg(feature = "svg")]
+use std::io;
 use std::{collections::HashMap, fmt};
 
+pub use diff::FlowchartDiff;
+#[cfg(feature = "render")]
+pub use layout::{EdgeLayout, FlowchartLayout, NodeLayout};
+pub use owned::{ConnectorOwned, FlowchartOwned, NodeOwned};
+pub use parse::{Error, ErrorKind, ParsedStatement, Statements};
+pub use query::CycleError;
+#[cfg(feature = "render")]
+pub use render::{boundary_point, draw_arrowhead, node_shape};
+pub use validate::Warning;
+
+/// The default style used with [`Flowchart::render`].
+#[cfg(feature = "render")]
+pub static DEFAULT_STYLE: Lazy<FlowchartStyle> = Lazy::new(FlowchartStyle::default);
+/// A default style for use with dark themes.
+#[cfg(feature = "render")]
+pub static DARK_STYLE: Lazy<FlowchartStyle> = Lazy::new(FlowchartStyle::default_dark);
+
 /// A flowchart
 ///
+/// The default is an empty flowchart flowing from top to bottom.
+///
 /// If any of the mutating methods return an error, the flowchart state is undefined and should be
-/// discarded. This doesn't affect memory safety (no `unsafe` is used).
+/// discarded. This doesn't affect memory safety (no `unsafe` is used). To keep the original,
+/// clone the flowchart before changing it.
+#[derive(Clone)]
 pub struct Flowchart<'input> {
     /// The direction this flowchart should be rendered in.
     pub direction: Direction,
@@ -21,7 +67,10 @@ pub struct Flowchart<'input> {
 }
 
 impl<'input> Flowchart<'input> {
-    fn new(direction: Direction) -> Self {
+    /// Create an empty flowchart, for building a chart in code rather than parsing it.
+    ///
+    /// Use [`Flowchart::add_node`] and [`Flowchart::add_edge`] to fill it in.
+    pub fn new(direction: Direction) -> Self {
         Flowchart {
             direction,
             graph: GraphMap::new(),
@@ -30,36 +79,171 @@ impl<'input> Flowchart<'input> {
     }
 
     /// Take textual input conforming to the mermaid spec and parse it into a [`Flowchart`].
-    pub fn parse<'a>(input: &'a str) -> Result<Flowchart<'a>> {
-        parse::parse_flowchart(input).map_err(|e| anyhow!("{}", e))
+    ///
+    /// Parse failures are an [`Error`], which gives the line and column the problem was found at,
+    /// and an [`ErrorKind`] saying what went wrong. To handle the statements one at a time without
+    /// building a flowchart, use [`Statements`].
+    pub fn parse<'a>(input: &'a str) -> Result<Flowchart<'a>, Error> {
+        parse::parse_flowchart(input)
+    }
+
+    /// Get a rough estimate of how expensive this chart will be to render.
+    pub fn complexity(&self) -> Complexity {
+        Complexity::layered(self.nodes.len(), self.graph.edge_count())
     }
 
-    fn add_node(&mut self, node: &Node<'input>) -> &'input str {
+    /// Add a node, or merge it with an existing node with the same id.
+    ///
+    /// A node that is just an id can be given a label and shape later on (or earlier on), but it's
+    /// an error ([`ErrorKind::ConflictingNode`]) to give the same node two different labels or
+    /// shapes. On success, returns the node's id.
+    pub fn add_node(&mut self, node: &Node<'input>) -> Result<&'input str, Error> {
         let id = node.id;
-        if node.is_id() {
-            // only insert the node if it's not there
-            self.nodes.entry(id).or_insert(node.clone());
-        } else {
-            // insert the node and panic if one is already there
-            if self.nodes.insert(id, node.clone()).is_some() {
-                panic!("node with given name already exists");
+        match self.nodes.get_mut(id) {
+            None => {
+                self.nodes.insert(id, *node);
+            }
+            Some(_) if node.is_id() => (),
+            Some(existing) if existing.is_id() => {
+                *existing = Node {
+                    inline_style: existing.inline_style.or(node.inline_style),
+                    ..*node
+                };
+            }
+            Some(existing) if existing.label == node.label && existing.style == node.style => (),
+            Some(existing) => {
+                return Err(ErrorKind::ConflictingNode {
+                    id: id.to_string(),
+                    first: (existing.label.to_string(), existing.style),
+                    second: (node.label.to_string(), node.style),
+                }
+                .into())
             }
         }
-        id
+        Ok(id)
     }
 
-    fn add_edge(&mut self, from: &'input str, to: &'input str, edge: Connector<'input>) {
-        assert!(self.nodes.contains_key(from) && self.nodes.contains_key(to));
-        if self.graph.add_edge(from, to, edge).is_some() {
-            panic!("edge already exists")
+    /// Add an edge between two nodes.
+    ///
+    /// Nodes that haven't been added yet are added with just their id. Only one edge can go from
+    /// one node to another, so it's an error ([`ErrorKind::DuplicateEdge`]) to add the same edge
+    /// twice.
+    pub fn add_edge(
+        &mut self,
+        from: &'input str,
+        to: &'input str,
+        edge: Connector<'input>,
+    ) -> Result<(), Error> {
+        if self.graph.contains_edge(from, to) {
+            return Err(ErrorKind::DuplicateEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+            }
+            .into());
+        }
+        for id in [from, to] {
+            self.nodes.entry(id).or_insert(Node {
+                id,
+                label: "",
+                style: NodeStyle::Square,
+                inline_style: None,
+            });
         }
+        self.graph.add_edge(from, to, edge);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "render")]
+impl<'input> Flowchart<'input> {
+    /// Use a [`piet::RenderContext`] to render this chart.
+    pub fn render<RC: RenderContext>(&self, ctx: &mut RC) -> Result<(), piet::Error> {
+        self.render_with_style(&DEFAULT_STYLE, ctx)
+    }
+
+    /// Like [`Flowchart::render`] but allows specifying a custom style.
+    pub fn render_with_style<RC: RenderContext>(
+        &self,
+        style: &FlowchartStyle,
+        ctx: &mut RC,
+    ) -> Result<(), piet::Error> {
+        self.render_with_direction(None, style, ctx)
+    }
+
+    /// Like [`Flowchart::render_with_style`], but flowing in `dir_override` if it is given,
+    /// rather than in the chart's own direction.
+    ///
+    /// This is useful for drawing charts the same way round, e.g. as thumbnails in a gallery.
+    /// The chart's direction isn't changed.
+    pub fn render_with_direction<RC: RenderContext>(
+        &self,
+        dir_override: Option<Direction>,
+        style: &FlowchartStyle,
+        ctx: &mut RC,
+    ) -> Result<(), piet::Error> {
+        let direction = dir_override.unwrap_or(self.direction);
+        let mut text = ctx.text().clone();
+        render::render(self, direction, style, ctx, &mut text)
+    }
+
+    /// Get the size of the area the chart will be drawn in with the given style.
+    ///
+    /// Text is measured using `ctx`, so this should be the context the chart will be rendered
+    /// with.
+    pub fn size<RC: RenderContext>(
+        &self,
+        style: &FlowchartStyle,
+        ctx: &mut RC,
+    ) -> Result<Size, piet::Error> {
+        let mut text = ctx.text().clone();
+        self.measure(style, &mut text)
+    }
+
+    /// Like [`Flowchart::size`], but measures text using a [`TextMeasurer`].
+    pub fn measure(
+        &self,
+        style: &FlowchartStyle,
+        text: &mut dyn TextMeasurer,
+    ) -> Result<Size, piet::Error> {
+        render::size(self, style, text)
+    }
+
+    /// Write out an svg image to `writer`, with optional custom styling.
+    ///
+    /// The canvas is sized to fit the chart. Each node and edge label gets a `<title>` with its
+    /// text (see `svg::insert_tooltips`). Nodes are also given an `id` of `node-` followed by the
+    /// node's id, with any characters that aren't allowed in an svg id replaced by `_`. The
+    /// background is transparent unless [`FlowchartStyle::background_color`] is set.
+    #[cfg(feature = "svg")]
+    pub fn to_svg(&self, writer: impl io::Write, style: Option<&FlowchartStyle>) -> io::Result<()> {
+        let style = style.unwrap_or(&DEFAULT_STYLE);
+        let mut rc = piet_svg::RenderContext::new(Size::ZERO);
+        let size = self.size(style, &mut rc).map_err(render_error)?;
+        svg::write_svg(writer, size, |rc| {
+            self.render_with_style(style, rc)?;
+            let mut text = rc.text().clone();
+            render::tooltips(self, style, &mut text)
+        })
+    }
+
+    /// Render an svg image to a string, with optional custom styling (see
+    /// [`Flowchart::to_svg`]).
+    #[cfg(feature = "svg")]
+    pub fn to_svg_string(&self, style: Option<&FlowchartStyle>) -> io::Result<String> {
+        let mut buf = vec![];
+        self.to_svg(&mut buf, style)?;
+        String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 }
 
 /// The direction the flowchart should be drawn in.
-#[derive(Debug, Copy, Clone)]
+///
+/// Directions can be parsed from, and written as, the names used in mermaid (e.g. `LR`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     /// Flow from top to bottom.
+    #[default]
     TopBottom,
     /// Flow from bottom to top.
     BottomTop,
@@ -69,18 +253,34 @@ pub enum Direction {
     RightLeft,
 }
 
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Direction::TopBottom => "TD",
+            Direction::BottomTop => "BT",
+            Direction::LeftRight => "LR",
+            Direction::RightLeft => "RL",
+        })
+    }
+}
+
 /// A node of the flowchart
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node<'input> {
     /// The node's id (mandatory)
     pub id: &'input str,
     /// The node's label.
     ///
     /// The empty string and no string are not disambiguated, for now. If this is empty, use the id
-    /// (see [`Node::label_or_id`])
+    /// (see [`Node::label_or_id`]). The label is as written in the source: use
+    /// [`unescape`](crate::text::unescape) to get the text to display.
     pub label: &'input str,
     /// The shape that should be used for the node.
     pub style: NodeStyle,
+    /// Colors etc. set for this node using a `style` statement (e.g.
+    /// `style A fill:#f9f,stroke:#333,stroke-width:4px`).
+    pub inline_style: Option<NodeInlineStyle>,
 }
 
 impl<'input> Node<'input> {
@@ -97,10 +297,17 @@ impl<'input> Node<'input> {
             self.label
         }
     }
+
+    /// Get the text to display for the node, with any markdown formatting (see
+    /// [`RichText::from_label`]).
+    pub fn rich_label(&self) -> RichText {
+        RichText::from_label(&unescape(self.label_or_id()))
+    }
 }
 
 /// The shape that the node should be drawn inside.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeStyle {
     /// A square node
     ///
@@ -138,8 +345,28 @@ pub enum NodeStyle {
     DoubleCircle,
 }
 
+/// Styling for a single node, from a `style` statement.
+///
+/// Only the declarations we understand are kept: anything else in the statement is ignored.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeInlineStyle {
+    /// The color to fill the node shape with (`fill`).
+    #[cfg_attr(feature = "serde", serde(with = "crate::style::serde_color", default))]
+    pub fill: Option<Color>,
+    /// The color of the outline of the node shape (`stroke`).
+    #[cfg_attr(feature = "serde", serde(with = "crate::style::serde_color", default))]
+    pub stroke: Option<Color>,
+    /// The width of the outline of the node shape (`stroke-width`).
+    pub stroke_width: Option<f64>,
+    /// The color of the label text (`color`).
+    #[cfg_attr(feature = "serde", serde(with = "crate::style::serde_color", default))]
+    pub color: Option<Color>,
+}
+
 /// Information associated with a connection between nodes (an edge).
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Connector<'input> {
     /// The style of the line.
     pub line_style: LineStyle,
@@ -153,9 +380,70 @@ pub struct Connector<'input> {
     ///
     /// This is used to hint to the layout engine which connections should be longer.
     pub rank: u16,
+    /// Colors etc. set for this edge using a `linkStyle` statement (e.g.
+    /// `linkStyle 0 stroke:#ff3,stroke-width:4px`).
+    pub inline_style: Option<EdgeInlineStyle>,
+}
+
+impl<'input> Connector<'input> {
+    /// Create a connector with the given line style, no arrows, no label and a rank of 1 (the
+    /// same as `---`).
+    pub fn new(line_style: LineStyle) -> Self {
+        Connector {
+            line_style,
+            arrow_start: None,
+            arrow_end: None,
+            label: "",
+            rank: 1,
+            inline_style: None,
+        }
+    }
+
+    /// Set the label.
+    pub fn with_label(mut self, label: &'input str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Set the rank (how long the connection should be, see [`Connector::rank`]).
+    pub fn with_rank(mut self, rank: u16) -> Self {
+        self.rank = rank;
+        self
+    }
+
+    /// Set the arrows at the "from" and "to" ends.
+    pub fn with_arrows(mut self, start: Option<ArrowStyle>, end: Option<ArrowStyle>) -> Self {
+        self.arrow_start = start;
+        self.arrow_end = end;
+        self
+    }
+
+    /// Set the colors etc. that would be set using a `linkStyle` statement.
+    pub fn with_inline_style(mut self, inline_style: EdgeInlineStyle) -> Self {
+        self.inline_style = Some(inline_style);
+        self
+    }
+}
+
+/// Styling for a single edge, from a `linkStyle` statement.
+///
+/// Only the declarations we understand are kept: anything else in the statement is ignored.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgeInlineStyle {
+    /// The color of the line (`stroke`).
+    #[cfg_attr(feature = "serde", serde(with = "crate::style::serde_color", default))]
+    pub stroke: Option<Color>,
+    /// The width of the line (`stroke-width`).
+    pub stroke_width: Option<f64>,
+    /// The length of each dash and of the gaps between them (`stroke-dasharray`).
+    ///
+    /// A single length is used for both. Only the first two lengths are kept.
+    pub dash: Option<[f64; 2]>,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LineStyle {
     /// A normal solid line.
     ///
@@ -168,6 +456,7 @@ pub enum LineStyle {
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArrowStyle {
     /// An arrowhead that looks like an arrow.
     Arrow,
@@ -177,6 +466,182 @@ pub enum ArrowStyle {
     Cross,
 }
 
+/// How to draw a flowchart.
+///
+/// `style` and `linkStyle` statements in the source override these settings for the nodes and
+/// edges they apply to.
+#[cfg(feature = "render")]
+#[derive(Debug, Clone)]
+pub struct FlowchartStyle {
+    /// The background color (see [`clear_background`](crate::style::clear_background)).
+    pub background_color: Color,
+    /// The space left around the edge of the chart.
+    pub margin: f64,
+    /// The color to fill nodes with.
+    pub node_fill: Color,
+    /// How to style the outline of nodes.
+    pub node_outline: StrokeStyle,
+    /// How to style the text inside nodes.
+    pub node_label: TextStyle,
+    /// The space between a node's label and its outline.
+    pub node_padding: f64,
+    /// How to draw [`LineStyle::Normal`] edges.
+    pub edge_normal: StrokeStyle,
+    /// How to draw [`LineStyle::Thick`] edges.
+    pub edge_thick: StrokeStyle,
+    /// How to draw [`LineStyle::Dotted`] edges.
+    pub edge_dotted: StrokeStyle,
+    /// How to draw the ends of edges, for every line style.
+    ///
+    /// The default [`LineCap::Butt`] stops exactly where the edge meets a node or arrowhead,
+    /// which can leave a tiny gap. [`LineCap::Round`] gives a softer look.
+    pub edge_line_cap: LineCap,
+    /// How to style edge labels.
+    pub edge_label: TextStyle,
+    /// The color of the box drawn behind edge labels, so the edge doesn't run through the text.
+    pub edge_label_background: Color,
+}
+
+#[cfg(feature = "render")]
+impl FlowchartStyle {
+    /// The default style, based on the default `mermaid.js` theme.
+    pub fn default() -> Self {
+        let edge_color = Color::rgb8(0x33, 0x33, 0x33);
+        Self {
+            background_color: Color::TRANSPARENT,
+            margin: 10.,
+            node_fill: Color::rgb8(0xec, 0xec, 0xff),
+            node_outline: StrokeStyle::new(1., Color::rgb8(0x93, 0x70, 0xdb)),
+            node_label: TextStyle::default(),
+            node_padding: 10.,
+            edge_normal: StrokeStyle::new(2., edge_color),
+            edge_thick: StrokeStyle::new(3.5, edge_color),
+            edge_dotted: StrokeStyle::new(2., edge_color).with_dash([3., 3.]),
+            edge_line_cap: LineCap::Butt,
+            edge_label: TextStyle::default().with_font_size(14.),
+            edge_label_background: Color::rgba8(0xe8, 0xe8, 0xe8, 0xcc),
+        }
+    }
+
+    /// A default style for use with dark themes.
+    pub fn default_dark() -> Self {
+        let edge_color = Color::rgb8(0xcc, 0xcc, 0xcc);
+        Self {
+            node_fill: Color::rgb8(0x1f, 0x20, 0x20),
+            node_outline: StrokeStyle::new(1., Color::rgb8(0xcc, 0xcc, 0xcc)),
+            node_label: TextStyle::default_dark(),
+            edge_normal: StrokeStyle::new(2., edge_color),
+            edge_thick: StrokeStyle::new(3.5, edge_color),
+            edge_dotted: StrokeStyle::new(2., edge_color).with_dash([3., 3.]),
+            edge_label: TextStyle::default_dark().with_font_size(14.),
+            edge_label_background: Color::rgba8(0x58, 0x58, 0x58, 0xcc),
+            ..Self::default()
+        }
+    }
+
+    /// Set the color to clear the background with.
+    pub fn with_background_color(mut self, background_color: Color) -> Self {
+        self.background_color = background_color;
+        self
+    }
+
+    /// Set the color to fill nodes with.
+    pub fn with_node_fill(mut self, node_fill: Color) -> Self {
+        self.node_fill = node_fill;
+        self
+    }
+
+    /// Set how to style the outline of nodes.
+    pub fn with_node_outline(mut self, node_outline: StrokeStyle) -> Self {
+        self.node_outline = node_outline;
+        self
+    }
+
+    /// Set how to style the text inside nodes.
+    pub fn with_node_label(mut self, node_label: TextStyle) -> Self {
+        self.node_label = node_label;
+        self
+    }
+
+    /// Set how to draw the ends of edges.
+    pub fn with_edge_line_cap(mut self, edge_line_cap: LineCap) -> Self {
+        self.edge_line_cap = edge_line_cap;
+        self
+    }
+
+    /// Set how to style edge labels.
+    pub fn with_edge_label(mut self, edge_label: TextStyle) -> Self {
+        self.edge_label = edge_label;
+        self
+    }
+
+    /// Set the color of the box drawn behind edge labels.
+    ///
+    /// Use [`Color::TRANSPARENT`] to leave it out, so the edge shows through the label.
+    pub fn with_edge_label_background(mut self, edge_label_background: Color) -> Self {
+        self.edge_label_background = edge_label_background;
+        self
+    }
+
+    /// The color to fill `node` with, taking its `style` statement into account.
+    pub fn node_fill(&self, node: &Node) -> Color {
+        node.inline_style
+            .and_then(|inline| inline.fill)
+            .unwrap_or(self.node_fill)
+    }
+
+    /// How to draw the outline of `node`, taking its `style` statement into account.
+    pub fn node_outline(&self, node: &Node) -> StrokeStyle {
+        let mut outline = self.node_outline.clone();
+        if let Some(inline) = node.inline_style {
+            if let Some(stroke) = inline.stroke {
+                outline.color = stroke;
+            }
+            if let Some(width) = inline.stroke_width {
+                outline.width = width;
+            }
+        }
+        outline
+    }
+
+    /// How to style the label of `node`, taking its `style` statement into account.
+    pub fn node_label(&self, node: &Node) -> TextStyle {
+        let mut label = self.node_label.clone();
+        if let Some(color) = node.inline_style.and_then(|inline| inline.color) {
+            label.color = color;
+        }
+        label
+    }
+
+    /// How to draw an edge, taking its line style and `linkStyle` statement into account.
+    pub fn edge_stroke(&self, conn: &Connector) -> StrokeStyle {
+        let mut stroke = match conn.line_style {
+            LineStyle::Normal => self.edge_normal.clone(),
+            LineStyle::Thick => self.edge_thick.clone(),
+            LineStyle::Dotted => self.edge_dotted.clone(),
+        };
+        stroke.line_cap = self.edge_line_cap;
+        if let Some(inline) = conn.inline_style {
+            if let Some(color) = inline.stroke {
+                stroke.color = color;
+            }
+            if let Some(width) = inline.stroke_width {
+                stroke.width = width;
+            }
+            if let Some(dash) = inline.dash {
+                stroke.dash = Some(dash.to_vec());
+            }
+        }
+        stroke
+    }
+}
+
+impl Default for Flowchart<'_> {
+    fn default() -> Self {
+        Flowchart::new(Direction::default())
+    }
+}
+
 impl fmt::Debug for Flowchart<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // is there a better way of doing this? I wish there was. Sigh.
@@ -216,3 +681,110 @@ impl fmt::Debug for Flowchart<'_> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_in_code() {
+        let mut chart = Flowchart::new(Direction::LeftRight);
+        let start = Node {
+            id: "start",
+            label: "Start",
+            style: NodeStyle::Round,
+            inline_style: None,
+        };
+        assert_eq!(chart.add_node(&start).unwrap(), "start");
+        chart
+            .add_edge("start", "end", Connector::new(LineStyle::Normal))
+            .unwrap();
+        // the end node is added with just its id
+        assert_eq!(chart.nodes["end"].label, "");
+        assert!(chart
+            .add_edge("start", "end", Connector::new(LineStyle::Dotted))
+            .is_err());
+
+        let src = chart.to_mermaid();
+        let parsed = Flowchart::parse(&src).unwrap();
+        assert_eq!(parsed.direction, Direction::LeftRight);
+        assert_eq!(parsed.nodes["start"], start);
+        assert!(parsed.graph.contains_edge("start", "end"));
+    }
+
+    #[test]
+    fn direction_from_str() {
+        assert_eq!("LR".parse::<Direction>().unwrap(), Direction::LeftRight);
+        assert_eq!("TB".parse::<Direction>().unwrap(), Direction::TopBottom);
+        assert!("LR2".parse::<Direction>().is_err());
+        assert!("".parse::<Direction>().is_err());
+        assert_eq!(Direction::default(), Direction::TopBottom);
+        assert_eq!(Flowchart::default().direction, Direction::TopBottom);
+    }
+
+    #[test]
+    fn direction_display_round_trip() {
+        for direction in [
+            Direction::TopBottom,
+            Direction::BottomTop,
+            Direction::LeftRight,
+            Direction::RightLeft,
+        ] {
+            assert_eq!(
+                direction.to_string().parse::<Direction>().unwrap(),
+                direction
+            );
+        }
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_titles() {
+        let chart =
+            Flowchart::parse("flowchart LR\n  A[Start] -->|go on| B\n  B --> C(a & b)").unwrap();
+        let svg = chart.to_svg_string(None).unwrap();
+        assert!(svg.contains("<path id=\"node-A\" "));
+        assert!(svg.contains("<title>Start</title>"));
+        // nodes without a label are described by their id
+        assert!(svg.contains("<path id=\"node-B\" "));
+        assert!(svg.contains("<title>B</title>"));
+        assert!(svg.contains("<title>a &amp; b</title>"));
+        // edge labels get a title but no id
+        assert!(svg.contains("<title>go on</title>"));
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn edge_line_cap() {
+        let conn = Connector::new(LineStyle::Dotted);
+        let style = FlowchartStyle::default();
+        assert_eq!(style.edge_stroke(&conn).line_cap, LineCap::Butt);
+        let style = style.with_edge_line_cap(LineCap::Round);
+        assert_eq!(style.edge_stroke(&conn).line_cap, LineCap::Round);
+        // the dash pattern of dotted lines is kept
+        assert!(style.edge_stroke(&conn).dash.is_some());
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn render_with_direction() {
+        let chart = Flowchart::parse("flowchart LR\nA --> B").unwrap();
+        // node labels are drawn at the center of each node
+        let centers = |dir_override| {
+            let mut rc = crate::test_util::Recorder::new();
+            chart
+                .render_with_direction(dir_override, &DEFAULT_STYLE, &mut rc)
+                .unwrap();
+            let texts = rc.texts();
+            assert_eq!(texts.len(), 2);
+            (texts[0], texts[1])
+        };
+        let (a, b) = centers(None);
+        assert_eq!(a.y, b.y);
+        assert_ne!(a.x, b.x);
+        let (a, b) = centers(Some(Direction::TopBottom));
+        assert_eq!(a.x, b.x);
+        assert_ne!(a.y, b.y);
+        assert_eq!(chart.direction, Direction::LeftRight);
+    }
+}
@@ -0,0 +1,212 @@
+use super::{
+    ArrowStyle, Connector, Direction, EdgeInlineStyle, Error, ErrorKind, Flowchart, LineStyle,
+    Node, NodeInlineStyle, NodeStyle,
+};
+use std::{collections::HashMap, str::FromStr};
+
+/// A version of [`Flowchart`] that owns its data, so it can outlive the input it was parsed from.
+///
+/// Use [`Flowchart::into_owned`] or [`str::parse`] to create one, and
+/// [`FlowchartOwned::as_flowchart`] to get back a [`Flowchart`] borrowing from it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlowchartOwned {
+    /// The direction this flowchart should be rendered in.
+    pub direction: Direction,
+    /// The nodes of the flowchart, keyed by id.
+    pub nodes: HashMap<String, NodeOwned>,
+    /// The edges of the flowchart, as `(from, to, connector)`.
+    pub edges: Vec<(String, String, ConnectorOwned)>,
+}
+
+/// An owned version of [`Node`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeOwned {
+    /// The node's id.
+    pub id: String,
+    /// The node's label (see [`Node::label`]).
+    pub label: String,
+    /// The shape that should be used for the node.
+    pub style: NodeStyle,
+    /// Colors etc. set for this node using a `style` statement.
+    pub inline_style: Option<NodeInlineStyle>,
+}
+
+/// An owned version of [`Connector`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectorOwned {
+    /// The style of the line.
+    pub line_style: LineStyle,
+    /// What style (if any) should be used for the "from" arrow
+    pub arrow_start: Option<ArrowStyle>,
+    /// What style (if any) should be used for the "to" arrow
+    pub arrow_end: Option<ArrowStyle>,
+    /// An optional label
+    pub label: String,
+    /// The rank of the connection (see [`Connector::rank`]).
+    pub rank: u16,
+    /// Colors etc. set for this edge using a `linkStyle` statement.
+    pub inline_style: Option<EdgeInlineStyle>,
+}
+
+impl Flowchart<'_> {
+    /// Copy all the data borrowed from the input, so the chart can outlive it.
+    pub fn into_owned(self) -> FlowchartOwned {
+        FlowchartOwned {
+            direction: self.direction,
+            nodes: self
+                .nodes
+                .into_values()
+                .map(|node| (node.id.to_string(), node.into_owned()))
+                .collect(),
+            edges: self
+                .graph
+                .all_edges()
+                .map(|(from, to, conn)| (from.to_string(), to.to_string(), conn.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl FlowchartOwned {
+    /// Get a [`Flowchart`] that borrows its data from `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same edge is in `self.edges` more than once.
+    pub fn as_flowchart(&self) -> Flowchart<'_> {
+        let mut chart = Flowchart::new(self.direction);
+        for node in self.nodes.values() {
+            // ids are unique, so nodes can't conflict
+            chart.add_node(&node.as_node()).unwrap();
+        }
+        for (from, to, conn) in &self.edges {
+            chart.add_edge(from, to, conn.as_connector()).unwrap();
+        }
+        chart
+    }
+
+    /// Add the nodes and edges of `other` to this chart. The direction of `self` is kept.
+    ///
+    /// If `prefix` is given, it is added to the start of every id from `other` (e.g. to keep apart
+    /// charts generated from the same template). It's an error for the two charts to have a node
+    /// id in common after prefixing, in which case `self` is left unchanged.
+    pub fn merge(&mut self, other: FlowchartOwned, prefix: Option<&str>) -> Result<(), Error> {
+        let prefix = prefix.unwrap_or("");
+        let rename = |id: String| {
+            if prefix.is_empty() {
+                id
+            } else {
+                format!("{}{}", prefix, id)
+            }
+        };
+        let nodes = other
+            .nodes
+            .into_values()
+            .map(|node| NodeOwned {
+                id: rename(node.id),
+                ..node
+            })
+            .collect::<Vec<_>>();
+        let clash = nodes
+            .iter()
+            .map(|node| &node.id)
+            .filter(|id| self.nodes.contains_key(*id))
+            .min();
+        if let Some(id) = clash {
+            return Err(ErrorKind::DuplicateNode(id.clone()).into());
+        }
+
+        self.nodes
+            .extend(nodes.into_iter().map(|node| (node.id.clone(), node)));
+        self.edges.extend(
+            other
+                .edges
+                .into_iter()
+                .map(|(from, to, conn)| (rename(from), rename(to), conn)),
+        );
+        Ok(())
+    }
+}
+
+impl FromStr for FlowchartOwned {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Flowchart::parse(s)?.into_owned())
+    }
+}
+
+impl Node<'_> {
+    /// Copy the data borrowed from the input.
+    pub fn into_owned(self) -> NodeOwned {
+        NodeOwned {
+            id: self.id.to_string(),
+            label: self.label.to_string(),
+            style: self.style,
+            inline_style: self.inline_style,
+        }
+    }
+}
+
+impl NodeOwned {
+    /// Get a [`Node`] that borrows its data from `self`.
+    pub fn as_node(&self) -> Node<'_> {
+        Node {
+            id: &self.id,
+            label: &self.label,
+            style: self.style,
+            inline_style: self.inline_style,
+        }
+    }
+}
+
+impl Connector<'_> {
+    /// Copy the data borrowed from the input.
+    pub fn into_owned(self) -> ConnectorOwned {
+        ConnectorOwned {
+            line_style: self.line_style,
+            arrow_start: self.arrow_start,
+            arrow_end: self.arrow_end,
+            label: self.label.to_string(),
+            rank: self.rank,
+            inline_style: self.inline_style,
+        }
+    }
+}
+
+impl ConnectorOwned {
+    /// Get a [`Connector`] that borrows its data from `self`.
+    pub fn as_connector(&self) -> Connector<'_> {
+        Connector {
+            line_style: self.line_style,
+            arrow_start: self.arrow_start,
+            arrow_end: self.arrow_end,
+            label: &self.label,
+            rank: self.rank,
+            inline_style: self.inline_style,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_clash() {
+        let mut first = "flowchart TD\nA --> B".parse::<FlowchartOwned>().unwrap();
+        let second = "flowchart TD\nB --> C".parse::<FlowchartOwned>().unwrap();
+
+        let err = first.merge(second.clone(), None).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DuplicateNode("B".into()));
+        assert_eq!(first.nodes.len(), 2);
+
+        first.merge(second, Some("other_")).unwrap();
+        assert_eq!(first.nodes.len(), 4);
+        assert_eq!(first.edges.len(), 2);
+        assert!(first.nodes.contains_key("other_B"));
+    }
+}
@@ -1,64 +1,440 @@
-use super::{ArrowStyle, Connector, Direction, Flowchart, LineStyle, Node, NodeStyle};
-use anyhow::{anyhow, Result};
+use super::{
+    ArrowStyle, Connector, Direction, EdgeInlineStyle, Flowchart, LineStyle, Node, NodeInlineStyle,
+    NodeStyle,
+};
+use crate::{style::parse_hex_color, text::closing_quote};
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{alphanumeric1, multispace0, space0},
-    combinator::{opt, value},
-    multi::many1_count,
-    Finish, IResult,
+    bytes::complete::{tag, tag_no_case, take_till1, take_until, take_while, take_while1},
+    character::complete::{digit1, multispace0, multispace1, one_of, space0, space1},
+    combinator::{all_consuming, cut, eof, map, map_res, opt, peek, recognize, value},
+    error::{FromExternalError, ParseError},
+    multi::{many0_count, many1_count, separated_list1},
+    sequence::{delimited, pair},
+    Finish, InputTake, Slice,
 };
+use nom_locate::LocatedSpan;
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+/// If parsing failed, this type contains a description of the reason for the failure and the
+/// location failure occurred at.
+#[derive(Debug)]
+pub struct Error {
+    /// (1-indexed) line number of the error, or 0 if the error didn't come from parsing.
+    pub line: u32,
+    /// (1-indexed) column number of the error, counting characters rather than bytes, or 0 if the
+    /// error didn't come from parsing.
+    pub col: usize,
+    /// (0-indexed) offset in the input string of the error.
+    pub offset: usize,
+    kind: ErrorKind,
+}
+
+impl Error {
+    fn new(span: &Span<'_>, kind: ErrorKind) -> Self {
+        Self {
+            line: span.location_line(),
+            col: span.get_utf8_column(),
+            offset: span.location_offset(),
+            kind,
+        }
+    }
+
+    /// Get a description of the failure.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Move the error to `span`, for errors found after the statement they come from was parsed.
+    fn at(self, span: &Span<'_>) -> Self {
+        Self::new(span, self.kind)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    /// An error that doesn't come from parsing, e.g. from building a [`Flowchart`] in code.
+    ///
+    /// There's no input for it to be in, so the line and column are 0.
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            line: 0,
+            col: 0,
+            offset: 0,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}", self.kind);
+        }
+        write!(f, "on line {}, col {}: {}", self.line, self.col, self.kind)
+    }
+}
+impl std::error::Error for Error {}
+
+/// Different types of errors for the flowchart, mostly from parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The input didn't start with `flowchart` or `graph`.
+    ExpectedHeader,
+    /// A direction (in the header or a `direction` statement) wasn't one we know.
+    ///
+    /// The inner value is the direction as written, which is empty if it was missing.
+    UnknownDirection(String),
+    /// Expected a node id, like `A` or `node_1`.
+    ExpectedNodeId,
+    /// Expected a link between nodes, like `-->`.
+    ExpectedLink,
+    /// A link mixed normal and thick line segments, like `-=>`.
+    MixedLineStyle,
+    /// Found an opening quote but no corresponding closing quote.
+    ///
+    /// The inner value is the closing quote that was expected (`"` or `|`).
+    UnclosedQuote(&'static str),
+    /// Found the start of a node shape, like `[` or `((`, but not the matching end.
+    ///
+    /// The inner value is the end that was expected.
+    UnclosedShape(&'static str),
+    /// A node was given two different labels or shapes.
+    ///
+    /// When parsing, the error is reported on the statement with the second definition.
+    ConflictingNode {
+        /// The id of the node.
+        id: String,
+        /// The label and shape the node was given first.
+        first: (String, NodeStyle),
+        /// The label and shape that don't match them.
+        second: (String, NodeStyle),
+    },
+    /// The same two nodes were linked more than once, in the same direction.
+    DuplicateEdge {
+        /// The id of the node the links start at.
+        from: String,
+        /// The id of the node the links end at.
+        to: String,
+    },
+    /// Two flowcharts being merged (see [`FlowchartOwned::merge`](super::FlowchartOwned::merge)) both have a node with this id.
+    DuplicateNode(String),
+    /// Found some input that doesn't fit anywhere in the grammar.
+    ///
+    /// The inner value is the rest of the line, which is empty at the end of a line.
+    Unexpected(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const DIRECTIONS: &str = "`TB` (or `TD`), `BT`, `LR` or `RL`";
+        match self {
+            ErrorKind::ExpectedHeader => write!(f, "expected `flowchart` or `graph`"),
+            ErrorKind::UnknownDirection(dir) if dir.is_empty() => {
+                write!(f, "expected a direction, one of {}", DIRECTIONS)
+            }
+            ErrorKind::UnknownDirection(dir) => {
+                write!(
+                    f,
+                    "unknown direction `{}`, expected one of {}",
+                    dir, DIRECTIONS
+                )
+            }
+            ErrorKind::ExpectedNodeId => write!(f, "expected a node id"),
+            ErrorKind::ExpectedLink => write!(f, "expected a link, like `-->`"),
+            ErrorKind::MixedLineStyle => write!(f, "mixed `-` and `=` in the same link"),
+            ErrorKind::UnclosedQuote(lit) => {
+                write!(f, "unclosed quoted string (expected {:?}, found EOL)", lit)
+            }
+            ErrorKind::UnclosedShape(lit) => {
+                write!(f, "unclosed node shape (expected {:?}, found EOL)", lit)
+            }
+            ErrorKind::ConflictingNode { id, first, second } => write!(
+                f,
+                "node `{}` is defined more than once, as `{}` ({:?}) and `{}` ({:?})",
+                id, first.0, first.1, second.0, second.1
+            ),
+            ErrorKind::DuplicateEdge { from, to } => {
+                write!(f, "`{}` is linked to `{}` more than once", from, to)
+            }
+            ErrorKind::DuplicateNode(id) => write!(f, "both charts have a node with id `{}`", id),
+            ErrorKind::Unexpected(found) if found.is_empty() => {
+                write!(f, "unexpected end of line")
+            }
+            ErrorKind::Unexpected(found) => write!(f, "couldn't parse `{}`", found),
+        }
+    }
+}
+
+impl<'input> ParseError<Span<'input>> for Error {
+    fn from_error_kind(input: Span<'input>, _kind: nom::error::ErrorKind) -> Self {
+        // nom's error kinds describe the parser that failed rather than the problem, so show
+        // what we found instead. Parsers with something more specific to say use `expect`.
+        let found = input.lines().next().unwrap_or("").trim();
+        Error::new(&input, ErrorKind::Unexpected(found.to_string()))
+    }
 
-struct ParseCtx<'input> {
+    fn append(_: Span<'input>, _: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'input, E> FromExternalError<Span<'input>, E> for Error {
+    fn from_external_error(input: Span<'input>, kind: nom::error::ErrorKind, _: E) -> Self {
+        Self::from_error_kind(input, kind)
+    }
+}
+
+type Span<'input> = LocatedSpan<&'input str>;
+type IResult<'input, Out> = nom::IResult<Span<'input>, Out, Error>;
+
+/// A single statement from a flowchart description, as produced by [`Statements`].
+///
+/// More kinds of statement may be added in future (e.g. for subgraphs), so matches should have a
+/// wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ParsedStatement<'input> {
+    /// A node, with any label and shape it was given.
+    ///
+    /// Every node a line mentions is produced before the edges that use it, so a node can appear
+    /// more than once. Mentions that are just the id have an empty label.
+    NodeDef(Node<'input>),
+    /// An edge between two nodes.
+    ///
+    /// Lines like `A & B --> C --> D` are split into one edge for each pair of nodes that are
+    /// connected.
+    Edge {
+        /// The id of the node the edge starts at.
+        from: &'input str,
+        /// The id of the node the edge ends at.
+        to: &'input str,
+        /// How the edge should be drawn.
+        connector: Connector<'input>,
+    },
+    /// A `direction` statement, changing the direction of the flowchart.
+    Direction(Direction),
+    /// A `style` statement, giving a node an inline style.
+    Style {
+        /// The id of the node to style.
+        id: &'input str,
+        /// The style declarations that were understood.
+        style: NodeInlineStyle,
+    },
+    /// A `linkStyle` statement, giving edges an inline style.
+    LinkStyle {
+        /// The indices of the edges to style, in the order they were defined, or `None` for all
+        /// edges (`linkStyle default`).
+        indices: Option<Vec<usize>>,
+        /// The style declarations that were understood.
+        style: EdgeInlineStyle,
+    },
+}
+
+/// An iterator over the statements of a flowchart description.
+///
+/// This parses one line at a time, without building a [`Flowchart`], which is useful for very
+/// large charts that will be stored some other way. Iteration stops after the first error.
+pub struct Statements<'input> {
+    direction: Direction,
+    /// The input that hasn't been split into lines yet, or `None` when there are no more lines.
+    rest: Option<Span<'input>>,
+    /// Statements from the current line that haven't been returned yet, with the line they are
+    /// from.
+    pending: VecDeque<(Span<'input>, ParsedStatement<'input>)>,
     left_node_scratch: Vec<Node<'input>>,
     right_node_scratch: Vec<Node<'input>>,
 }
 
-impl<'input> ParseCtx<'input> {
-    fn new() -> Self {
-        Self {
+impl<'input> Statements<'input> {
+    /// Parse the header of `input` (e.g. `flowchart LR`), ready to iterate over the statements
+    /// that follow.
+    pub fn new(input: &'input str) -> Result<Self, Error> {
+        let (rest, direction) = header(LocatedSpan::new(input)).finish()?;
+        Ok(Self {
+            direction,
+            rest: Some(rest),
+            pending: VecDeque::new(),
             left_node_scratch: vec![],
             right_node_scratch: vec![],
+        })
+    }
+
+    /// The direction given in the header.
+    ///
+    /// This is the direction of the flowchart, unless it is changed by a
+    /// [`ParsedStatement::Direction`].
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Get the next statement, along with the (trimmed) line it is from.
+    fn next_statement(&mut self) -> Option<Result<(Span<'input>, ParsedStatement<'input>), Error>> {
+        while self.pending.is_empty() {
+            let line = self.next_line()?;
+            if line.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.parse_statements(line) {
+                // stop at the first error
+                self.rest = None;
+                self.pending.clear();
+                return Some(Err(e));
+            }
         }
+        self.pending.pop_front().map(Ok)
     }
-    fn scratches(&mut self) -> (&mut Vec<Node<'input>>, &mut Vec<Node<'input>>) {
-        (&mut self.left_node_scratch, &mut self.right_node_scratch)
+
+    /// Get the next line of the input, with whitespace trimmed from each end.
+    fn next_line(&mut self) -> Option<Span<'input>> {
+        let rest = self.rest?;
+        let line = match rest.find('\n') {
+            Some(idx) => {
+                self.rest = Some(rest.slice(idx + 1..));
+                rest.slice(..idx)
+            }
+            None => {
+                self.rest = None;
+                rest
+            }
+        };
+        let trimmed_start = line.len() - line.trim_start().len();
+        Some(line.slice(trimmed_start..trimmed_start + line.trim().len()))
+    }
+
+    /// Parse the statements on a line into `self.pending`.
+    fn parse_statements(&mut self, line: Span<'input>) -> Result<(), Error> {
+        if let Ok((_, (id, style))) = style_statement(line) {
+            self.pending
+                .push_back((line, ParsedStatement::Style { id, style }));
+        } else if let Ok((_, (indices, style))) = link_style_statement(line) {
+            self.pending
+                .push_back((line, ParsedStatement::LinkStyle { indices, style }));
+        } else if let Ok((_, token)) = direction_statement(line) {
+            // TODO apply to the enclosing subgraph, once we support subgraphs
+            let (_, direction) = known_direction(token).finish()?;
+            self.pending
+                .push_back((line, ParsedStatement::Direction(direction)));
+        } else {
+            parse_line(self, line).finish()?;
+        }
+        Ok(())
     }
 }
 
-pub fn parse_flowchart(input: &str) -> Result<Flowchart<'_>, nom::error::Error<&str>> {
-    let mut ctx = ParseCtx::new();
-    let (_, chart) = flowchart(&mut ctx, input).finish()?;
-    Ok(chart)
+impl<'input> Iterator for Statements<'input> {
+    type Item = Result<ParsedStatement<'input>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_statement()
+            .map(|res| res.map(|(_, statement)| statement))
+    }
 }
 
-// inner parse_flowchart
-fn flowchart<'input>(
-    ctx: &mut ParseCtx<'input>,
-    i: &'input str,
-) -> IResult<&'input str, Flowchart<'input>> {
+pub fn parse_flowchart(input: &str) -> Result<Flowchart<'_>, Error> {
+    let mut statements = Statements::new(input)?;
+    let mut flow = Flowchart::new(statements.direction());
+    // Styles from `style` statements, which are applied once all the nodes have been defined.
+    let mut styles = vec![];
+    // Styles from `linkStyle` statements, which are applied once all the edges have been
+    // defined. `None` means the style is the default for all edges.
+    let mut link_styles = vec![];
+
+    while let Some(statement) = statements.next_statement() {
+        let (line, statement) = statement?;
+        match statement {
+            ParsedStatement::NodeDef(node) => {
+                flow.add_node(&node).map_err(|e| e.at(&line))?;
+            }
+            ParsedStatement::Edge {
+                from,
+                to,
+                connector,
+            } => flow
+                .add_edge(from, to, connector)
+                .map_err(|e| e.at(&line))?,
+            ParsedStatement::Direction(direction) => flow.direction = direction,
+            ParsedStatement::Style { id, style } => styles.push((id, style)),
+            ParsedStatement::LinkStyle { indices, style } => link_styles.push((indices, style)),
+        }
+    }
+    for (id, style) in styles {
+        // like mermaid.js, styling a node that doesn't exist creates it
+        let node = flow.nodes.entry(id).or_insert(Node {
+            id,
+            label: "",
+            style: NodeStyle::Square,
+            inline_style: None,
+        });
+        let old = node.inline_style.unwrap_or_default();
+        node.inline_style = Some(NodeInlineStyle {
+            fill: style.fill.or(old.fill),
+            stroke: style.stroke.or(old.stroke),
+            stroke_width: style.stroke_width.or(old.stroke_width),
+            color: style.color.or(old.color),
+        });
+    }
+
+    // edges are numbered in the order they were defined, which is the order `GraphMap` keeps them
+    let edges = flow
+        .graph
+        .all_edges()
+        .map(|(from, to, _)| (from, to))
+        .collect::<Vec<_>>();
+    let mut default_style = None;
+    for (indices, style) in link_styles {
+        match indices {
+            Some(indices) => {
+                // indices that don't match an edge are ignored
+                for (from, to) in indices.into_iter().filter_map(|idx| edges.get(idx)) {
+                    let conn = flow.graph.edge_weight_mut(from, to).unwrap();
+                    conn.inline_style = Some(merge_edge_style(conn.inline_style, style));
+                }
+            }
+            None => default_style = Some(merge_edge_style(default_style, style)),
+        }
+    }
+    if let Some(default_style) = default_style {
+        // styles for particular edges take priority over the default
+        for (from, to) in edges {
+            let conn = flow.graph.edge_weight_mut(from, to).unwrap();
+            let style = conn.inline_style.unwrap_or_default();
+            conn.inline_style = Some(merge_edge_style(Some(default_style), style));
+        }
+    }
+    Ok(flow)
+}
+
+/// Parse the header (e.g. `flowchart LR`), returning the initial direction.
+fn header(i: Span) -> IResult<Direction> {
     let (i, _) = multispace0(i)?;
-    let (i, _) = flowchart_tok(i)?;
+    let (i, _) = expect(ErrorKind::ExpectedHeader, flowchart_tok)(i)?;
     let (i, _) = ws(i)?;
-    let (i, direction) = direction(i)?;
+    let (i, direction) = known_direction(i)?;
+    // The first statement can be on the same line as the header (`flowchart LR A-->B`), but it
+    // must be separated from the direction.
+    let (i, _) = alt((multispace1, eof))(i)?;
+    Ok((i, direction))
+}
 
-    let mut flow = Flowchart::new(direction);
-    for line in i.lines() {
-        let line = line.trim();
-        if !line.is_empty() {
-            parse_line(ctx, line, &mut flow)?;
-        }
+/// Combine two edge styles, with declarations in `new` taking priority.
+fn merge_edge_style(old: Option<EdgeInlineStyle>, new: EdgeInlineStyle) -> EdgeInlineStyle {
+    let old = old.unwrap_or_default();
+    EdgeInlineStyle {
+        stroke: new.stroke.or(old.stroke),
+        stroke_width: new.stroke_width.or(old.stroke_width),
+        dash: new.dash.or(old.dash),
     }
-    Ok((i, flow))
 }
 
-/// Parse the flowchart token
-fn flowchart_tok(i: &str) -> IResult<&str, &str> {
-    tag("flowchart")(i)
+/// Parse the flowchart token (`graph` is an older name for the same thing)
+///
+/// Like mermaid, the token isn't case sensitive.
+fn flowchart_tok(i: Span) -> IResult<Span> {
+    alt((tag_no_case("flowchart"), tag_no_case("graph")))(i)
 }
 
 /// Parse the flowchart direction
-fn direction(i: &str) -> IResult<&str, Direction> {
+fn direction(i: Span) -> IResult<Direction> {
     alt((
         value(Direction::TopBottom, alt((tag("TB"), tag("TD")))),
         value(Direction::BottomTop, tag("BT")),
@@ -67,68 +443,201 @@ fn direction(i: &str) -> IResult<&str, Direction> {
     ))(i)
 }
 
-/// Parse a line of the source input.
+/// Parse a direction, failing with [`ErrorKind::UnknownDirection`] if it isn't one we know.
+///
+/// The whole alphanumeric token is checked, so `LRX` is an error rather than `LR` followed by
+/// `X`.
+fn known_direction(i: Span) -> IResult<Direction> {
+    let (rest, token) = take_while(char::is_alphanumeric)(i)?;
+    match all_consuming(direction)(token) {
+        Ok((_, direction)) => Ok((rest, direction)),
+        Err(_) => {
+            let kind = ErrorKind::UnknownDirection(token.fragment().to_string());
+            Err(nom::Err::Failure(Error::new(&token, kind)))
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = Error;
+
+    /// Parse a direction as written in mermaid (`TB` or `TD`, `BT`, `LR` or `RL`).
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (_, direction) = all_consuming(known_direction)(LocatedSpan::new(s)).finish()?;
+        Ok(direction)
+    }
+}
+
+/// Parse a `direction` statement (e.g. `direction LR`), returning the direction token.
+///
+/// The token isn't checked here, so that we can give a helpful error if it's wrong.
+fn direction_statement(i: Span) -> IResult<Span> {
+    let (i, _) = tag("direction")(i)?;
+    let (i, _) = space1(i)?;
+    let (i, token) = take_while1(char::is_alphanumeric)(i)?;
+    let (i, _) = eof(i)?;
+    Ok((i, token))
+}
+
+/// Parse a `style` statement (e.g. `style A fill:#f9f,stroke:#333,stroke-width:4px`).
+///
+/// Declarations we don't understand, or with values we can't parse, are ignored.
+fn style_statement(i: Span) -> IResult<(&str, NodeInlineStyle)> {
+    let (i, _) = tag("style")(i)?;
+    let (i, _) = space1(i)?;
+    let (i, id) = ident(i)?;
+    let (i, _) = space1(i)?;
+    let (i, decls) = i.take_split(i.len());
+
+    let mut style = NodeInlineStyle::default();
+    for (key, value) in style_decls(&decls) {
+        match key {
+            "fill" => style.fill = parse_hex_color(value).ok().or(style.fill),
+            "stroke" => style.stroke = parse_hex_color(value).ok().or(style.stroke),
+            "stroke-width" => style.stroke_width = parse_length(value).or(style.stroke_width),
+            "color" => style.color = parse_hex_color(value).ok().or(style.color),
+            _ => (),
+        }
+    }
+    Ok((i, (*id.fragment(), style)))
+}
+
+/// Parse a `linkStyle` statement (e.g. `linkStyle 0,2 stroke:#ff3,stroke-width:4px`).
+///
+/// The edges to style are given by index, or `default` for all of them (returned as `None`).
+/// Declarations we don't understand, or with values we can't parse, are ignored.
+fn link_style_statement(i: Span) -> IResult<(Option<Vec<usize>>, EdgeInlineStyle)> {
+    let (i, _) = tag("linkStyle")(i)?;
+    let (i, _) = space1(i)?;
+    let (i, indices) = alt((
+        value(None, tag("default")),
+        map(
+            separated_list1(tag(","), map_res(digit1, |idx: Span| idx.parse())),
+            Some,
+        ),
+    ))(i)?;
+    let (i, _) = space1(i)?;
+    let (i, decls) = i.take_split(i.len());
+
+    let mut style = EdgeInlineStyle::default();
+    for (key, value) in style_decls(&decls) {
+        match key {
+            "stroke" => style.stroke = parse_hex_color(value).ok().or(style.stroke),
+            "stroke-width" => style.stroke_width = parse_length(value).or(style.stroke_width),
+            "stroke-dasharray" => {
+                let lengths = value
+                    .split_whitespace()
+                    .map(parse_length)
+                    .collect::<Option<Vec<_>>>();
+                let dash = match lengths.as_deref() {
+                    Some(&[length]) => Some([length, length]),
+                    Some(&[dash, gap, ..]) => Some([dash, gap]),
+                    _ => None,
+                };
+                style.dash = dash.or(style.dash);
+            }
+            _ => (),
+        }
+    }
+    Ok((i, (indices, style)))
+}
+
+/// Split the declarations of a `style` or `linkStyle` statement into trimmed keys and values.
+fn style_decls(i: &str) -> impl Iterator<Item = (&str, &str)> {
+    i.trim_end_matches(';')
+        .split(',')
+        .filter_map(|decl| decl.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+/// Parse a length like `4` or `4px`.
+fn parse_length(value: &str) -> Option<f64> {
+    value.trim_end_matches("px").parse().ok()
+}
+
+/// Parse a line of the source input into node and edge statements.
 ///
 /// A line can have more than one connection in it. The line should already have been trimmed
 /// before calling this function.
 fn parse_line<'input>(
-    ctx: &mut ParseCtx<'input>,
-    i: &'input str,
-    flow: &mut Flowchart<'input>,
-) -> IResult<&'input str, ()> {
-    let (left_scratch, right_scratch) = ctx.scratches();
+    statements: &mut Statements<'input>,
+    line: Span<'input>,
+) -> IResult<'input, ()> {
+    let Statements {
+        pending,
+        left_node_scratch: left_scratch,
+        right_node_scratch: right_scratch,
+        ..
+    } = statements;
+    let mut push = |statement| pending.push_back((line, statement));
+    let i = line;
 
     // first connection
     let (i, left_nodes) = node_list(left_scratch, i)?;
     let (i, _) = ws(i)?;
-    let (i, conn) = connector(i)?;
+    if i.is_empty() {
+        // just defining nodes, without connecting them
+        for node in left_nodes {
+            push(ParsedStatement::NodeDef(*node));
+        }
+        return Ok((i, ()));
+    }
+    let (i, conn) = expect(ErrorKind::ExpectedLink, connector)(i)?;
     let (i, _) = ws(i)?;
     let (i, right_nodes) = node_list(right_scratch, i)?;
     let (mut i_outer, _) = ws(i)?;
-    for node in left_nodes {
-        flow.add_node(node);
-    }
-    for node in right_nodes {
-        flow.add_node(node);
-    }
-    for left in left_nodes {
-        for right in right_nodes {
-            flow.add_edge(left.id, right.id, conn);
-        }
+    for node in left_nodes.iter().chain(right_nodes) {
+        push(ParsedStatement::NodeDef(*node));
     }
+    push_edges(&mut push, left_nodes, right_nodes, conn);
 
     // 2nd+ connections (optional)
     while !i_outer.is_empty() {
-        // TODO we could avoid this copy by just switching which of the two vecs we consider the
-        // left one.
+        // The nodes on the right of the last connection are on the left of this one, so swap the
+        // scratch vecs rather than copying. `right_scratch` is cleared by `node_list` before being
+        // refilled, so every node on each side of every connection gets an edge.
         std::mem::swap(left_scratch, right_scratch);
         // The next line took the `&mut *` dance to convince the borrow checker (&mut isn't Copy,
         // so we need to reborrow).
         let left_nodes = &mut *left_scratch;
-        let (i, conn) = connector(i_outer)?;
+        let (i, conn) = expect(ErrorKind::ExpectedLink, connector)(i_outer)?;
         let (i, _) = ws(i)?;
         let (i, right_nodes) = node_list(right_scratch, i)?;
         let (i, _) = ws(i)?;
 
         i_outer = i;
         for node in right_nodes {
-            flow.add_node(node);
-        }
-        for left in left_nodes {
-            for right in right_nodes {
-                flow.add_edge(left.id, right.id, conn);
-            }
+            push(ParsedStatement::NodeDef(*node));
         }
+        push_edges(&mut push, left_nodes, right_nodes, conn);
     }
 
     Ok((i_outer, ()))
 }
 
+/// Make an edge statement from each node in `left` to each node in `right`.
+fn push_edges<'input>(
+    push: &mut impl FnMut(ParsedStatement<'input>),
+    left: &[Node<'input>],
+    right: &[Node<'input>],
+    connector: Connector<'input>,
+) {
+    for from in left {
+        for to in right {
+            push(ParsedStatement::Edge {
+                from: from.id,
+                to: to.id,
+                connector,
+            });
+        }
+    }
+}
+
 /// Parse a list of 1 or more nodes separated by `'&'`.
 fn node_list<'input, 'ctx>(
     nodes: &'ctx mut Vec<Node<'input>>,
-    i: &'input str,
-) -> IResult<&'input str, &'ctx [Node<'input>]> {
+    i: Span<'input>,
+) -> IResult<'input, &'ctx [Node<'input>]> {
     nodes.clear();
     let (i, first) = node(i)?;
     nodes.push(first);
@@ -145,58 +654,148 @@ fn node_list<'input, 'ctx>(
     Ok((i_outer, nodes))
 }
 
+/// The tags that can start a node shape.
+///
+/// Where one tag is a prefix of another (e.g. `[` and `[[`), the longer one must come first so it
+/// gets the first chance to match.
+const NODE_STYLE_STARTS: &[&str] = &[
+    "(((", "([", "[[", "[(", "((", "{{", "[/", r"[\", "[", "(", ">", "{",
+];
+
 /// Parse a node
-fn node(i: &str) -> IResult<&str, Node> {
-    let (i, id) = ident(i)?;
+fn node(i: Span) -> IResult<Node> {
+    let (i, id) = expect(ErrorKind::ExpectedNodeId, ident)(i)?;
+    let id = *id.fragment();
     let (i, _) = ws(i)?;
-    let (i, style_start) = opt(node_style_start)(i)?;
-    let style_start = match style_start {
-        Some(v) => v,
-        None => {
-            return Ok((
-                i,
-                Node {
-                    id,
-                    label: "",
-                    style: NodeStyle::Square,
-                },
-            ))
+    if i.starts_with("@{") {
+        let (i, (label, style)) = node_metadata(i)?;
+        let node = Node {
+            id,
+            // like mermaid.js, use the id if there's no label (so the shape isn't lost)
+            label: label.unwrap_or(id),
+            style,
+            inline_style: None,
+        };
+        return Ok((i, node));
+    }
+    // If the shape doesn't parse using the longest matching start tag, try shorter ones. This
+    // means e.g. `A[/path]` is a square node with the label `/path`, even though `[/` also starts
+    // a parallelogram.
+    let mut error = None;
+    for style_start in NODE_STYLE_STARTS {
+        if let Ok((rest, _)) = tag::<_, _, Error>(*style_start)(i) {
+            match node_shape(style_start, rest) {
+                Ok((i, (label, style))) => {
+                    let node = Node {
+                        id,
+                        label,
+                        style,
+                        inline_style: None,
+                    };
+                    return Ok((i, node));
+                }
+                Err(e) => error = Some(e),
+            }
         }
+    }
+    match error {
+        Some(e) => Err(e),
+        None => Ok((
+            i,
+            Node {
+                id,
+                label: "",
+                style: NodeStyle::Square,
+                inline_style: None,
+            },
+        )),
+    }
+}
+
+/// Parse node metadata, the newer way of giving a node a shape and label (e.g.
+/// `@{ shape: rounded, label: "Some text" }`).
+///
+/// Keys we don't understand, and shapes we don't know, are ignored.
+fn node_metadata(i: Span) -> IResult<(Option<&str>, NodeStyle)> {
+    let (mut i, _) = tag("@{")(i)?;
+    let mut label = None;
+    let mut style = NodeStyle::Square;
+    loop {
+        let (rest, _) = ws(i)?;
+        if let Ok((rest, _)) = tag::<_, _, Error>("}")(rest) {
+            return Ok((rest, (label, style)));
+        }
+        let (rest, key) = expect(
+            ErrorKind::UnclosedShape("}"),
+            take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_'),
+        )(rest)?;
+        let (rest, _) = ws(rest)?;
+        let (rest, _) = tag(":")(rest)?;
+        let (rest, _) = ws(rest)?;
+        let (rest, value) = if rest.starts_with('"') {
+            node_label_quoted(rest)?
+        } else {
+            take_till1(|c| c == ',' || c == '}')(rest)?
+        };
+        let value = value.fragment().trim_end();
+        match *key.fragment() {
+            "shape" => style = shape_by_name(value).unwrap_or(style),
+            "label" => label = Some(value),
+            _ => (),
+        }
+        let (rest, _) = ws(rest)?;
+        let (rest, _) = opt(tag(","))(rest)?;
+        i = rest;
+    }
+}
+
+/// The node style for a shape name used in node metadata (see [`node_metadata`]).
+///
+/// Shapes we don't have a style for map to the closest one we do.
+fn shape_by_name(name: &str) -> Option<NodeStyle> {
+    let style = match name {
+        "rect" | "rectangle" | "proc" | "process" => NodeStyle::Square,
+        "rounded" | "event" => NodeStyle::Round,
+        "stadium" | "pill" | "terminal" => NodeStyle::Stadium,
+        "subproc" | "subprocess" | "subroutine" | "fr-rect" | "framed-rectangle" => {
+            NodeStyle::Subroutine
+        }
+        "cyl" | "cylinder" | "db" | "database" => NodeStyle::Cylinder,
+        "circle" | "circ" | "sm-circ" | "small-circle" => NodeStyle::Circle,
+        "dbl-circ" | "double-circle" | "fr-circ" | "framed-circle" => NodeStyle::DoubleCircle,
+        "odd" => NodeStyle::Asymmetric,
+        "diam" | "diamond" | "decision" | "question" => NodeStyle::Rhombus,
+        "hex" | "hexagon" | "prepare" => NodeStyle::Hexagon,
+        "lean-r" | "lean-right" | "in-out" => NodeStyle::Parallelogram,
+        "lean-l" | "lean-left" | "out-in" => NodeStyle::ParallelogramRev,
+        "trap-b" | "trapezoid" | "trapezoid-bottom" | "priority" => NodeStyle::Trapezoid,
+        "trap-t" | "inv-trapezoid" | "trapezoid-top" | "manual" => NodeStyle::TrapezoidRev,
+        _ => return None,
     };
+    Some(style)
+}
+
+/// Parse the label and end tag of a node shape, after `style_start`.
+fn node_shape<'input>(
+    style_start: &str,
+    i: Span<'input>,
+) -> IResult<'input, (&'input str, NodeStyle)> {
     let (i, _) = ws(i)?;
-    let (i, label, style) = if matches!(i.chars().next(), Some('"')) {
+    if i.starts_with('"') {
         // quoted label
         let (i, label) = node_label_quoted(i)?;
         let (i, _) = ws(i)?;
         let (i, style) = node_style_end(style_start)(i)?;
-        (i, label, style)
+        Ok((i, (*label.fragment(), style)))
     } else {
-        let (i, (label, style)) = node_label_unquoted(style_start, i)?;
-        (i, label, style)
-    };
-    Ok((i, Node { id, label, style }))
-}
-
-fn node_style_start(i: &str) -> IResult<&str, &str> {
-    // TODO check order (longer before shorter)
-    alt((
-        tag("((("),
-        tag("(["),
-        tag("[["),
-        tag("[("),
-        tag("(("),
-        tag("{{"),
-        tag("[/"),
-        tag(r"[\"),
-        tag("["),
-        tag("("),
-        tag(">"),
-        tag("{"),
-    ))(i)
+        node_label_unquoted(style_start, i)
+    }
 }
 
-fn node_style_end<'a>(start: &str) -> impl FnMut(&'a str) -> IResult<&'a str, NodeStyle> {
-    // TODO check order (longer before shorter)
+/// Parse the end tag matching `start`.
+///
+/// Where more than one end is possible, longer ones must come first.
+fn node_style_end<'a>(start: &str) -> impl FnMut(Span<'a>) -> IResult<'a, NodeStyle> {
     match start {
         "[" => match_end_tester(&[("]", NodeStyle::Square)]),
         "(" => match_end_tester(&[(")", NodeStyle::Round)]),
@@ -222,48 +821,93 @@ fn node_style_end<'a>(start: &str) -> impl FnMut(&'a str) -> IResult<&'a str, No
 
 fn match_end_tester<'a>(
     tests: &'static [(&'static str, NodeStyle)],
-) -> impl Fn(&'a str) -> IResult<&'a str, NodeStyle> {
+) -> impl Fn(Span<'a>) -> IResult<'a, NodeStyle> {
     move |input| {
         for (test, style) in tests {
             if input.starts_with(test) {
-                return Ok((&input[test.len()..], *style));
+                return Ok((input.slice(test.len()..), *style));
             }
         }
-        Err(nom::Err::Error(nom::error::Error::new(
-            input,
-            nom::error::ErrorKind::Tag,
+        Err(nom::Err::Error(Error::new(
+            &input,
+            ErrorKind::UnclosedShape(tests[0].0),
         )))
     }
 }
 
-fn node_label_quoted(i: &str) -> IResult<&str, &str> {
-    let (i, _) = tag("\"")(i)?;
-    let mut iter = i.splitn(2, '"');
-    let inner = iter.next().expect("unreachable");
-    let i = iter.next().expect("TODO error handling");
-    Ok((i, inner))
+fn node_label_quoted(i: Span) -> IResult<Span> {
+    delimited(tag("\""), quoted_text, tag("\""))(i)
+}
+
+/// The text of a quoted string, up to the closing quote.
+///
+/// Quotes escaped with a backslash (`\"`) don't end the string, and are kept in the text (see
+/// [`crate::text::unescape`]).
+fn quoted_text(i: Span) -> IResult<Span> {
+    match closing_quote(&i) {
+        Some(end) => Ok(i.take_split(end)),
+        None => Err(nom::Err::Error(Error::new(
+            &i,
+            ErrorKind::UnclosedQuote("\""),
+        ))),
+    }
 }
 
 fn node_label_unquoted<'input>(
     style_start: &str,
-    i: &'input str,
-) -> IResult<&'input str, (&'input str, NodeStyle)> {
+    i: Span<'input>,
+) -> IResult<'input, (&'input str, NodeStyle)> {
     // I haven't done this using nom because honestly I don't know how to (without allocating a vec
     // using many0)
     let end_test = node_style_end(style_start);
-    input_until(end_test)(i)
+    let (i, (label, style)) = input_until(end_test)(i)?;
+    Ok((i, (*label.fragment(), style)))
 }
 
-fn connector(i: &str) -> IResult<&str, Connector> {
+fn connector(i: Span) -> IResult<Connector> {
     // The rules here are that if there is a starting arrow, then we take 1 off the calculated
     // rank, unless it is a dotted line, in which case there must be exactly 1 `-` either side of
     // the dots irrespective, and to get the rank we count the docs. So we split the two cases.
     //
-    // TODO we don't handle labels mid-way thru yet.
-    alt((connector_dotted, connector_solid))(i)
+    // TODO we don't handle labels mid-way thru yet (`A -- text --> B`).
+    let (i, mut conn) = alt((connector_dotted, connector_solid))(i)?;
+    let (i, _) = ws(i)?;
+    let (i, label) = opt(pipe_label)(i)?;
+    if let Some(label) = label {
+        conn.label = label;
+    }
+    Ok((i, conn))
 }
 
-fn connector_dotted(i: &str) -> IResult<&str, Connector> {
+/// A connector label between pipes (e.g. `|some text|`).
+///
+/// The label is taken verbatim (apart from surrounding whitespace), so it can contain characters
+/// that would be significant elsewhere, like brackets. If the label is quoted (`|"some text"|`),
+/// the quotes are removed and the label can also contain `|`.
+fn pipe_label(i: Span) -> IResult<&str> {
+    let (i, _) = tag("|")(i)?;
+    // once we've seen the opening pipe, this can't be anything other than a label
+    cut(expect(
+        ErrorKind::UnclosedQuote("|"),
+        alt((pipe_label_quoted, pipe_label_unquoted)),
+    ))(i)
+}
+
+fn pipe_label_quoted(i: Span) -> IResult<&str> {
+    let (i, _) = ws(i)?;
+    let (i, label) = delimited(tag("\""), quoted_text, tag("\""))(i)?;
+    let (i, _) = ws(i)?;
+    let (i, _) = tag("|")(i)?;
+    Ok((i, *label.fragment()))
+}
+
+fn pipe_label_unquoted(i: Span) -> IResult<&str> {
+    let (i, label) = take_until("|")(i)?;
+    let (i, _) = tag("|")(i)?;
+    Ok((i, label.fragment().trim()))
+}
+
+fn connector_dotted(i: Span) -> IResult<Connector> {
     let (i, arrow_start) = opt(arrow(true))(i)?;
     let (i, _) = tag("-")(i)?;
     let (i, rank) = many1_count(tag("."))(i)?;
@@ -278,18 +922,21 @@ fn connector_dotted(i: &str) -> IResult<&str, Connector> {
             arrow_end,
             label: "",
             rank: rank.try_into().expect("rank must be <= 65535"),
+            inline_style: None,
         },
     ))
 }
 
-fn connector_solid(i: &str) -> IResult<&str, Connector> {
+fn connector_solid(i: Span) -> IResult<Connector> {
+    let start = i;
+    let mixed = |kind| nom::Err::Failure(Error::new(&start, kind));
     let mut line_ty = LineTy::new();
     let (i, arrow_start) = opt(arrow(true))(i)?;
 
     // if no arrow, there is an extra line segment
     let i = if arrow_start.is_none() {
         let (i, style) = line(i)?;
-        line_ty.set(style).expect("TODO error handling");
+        line_ty.set(style).map_err(mixed)?;
         i
     } else {
         i
@@ -297,11 +944,11 @@ fn connector_solid(i: &str) -> IResult<&str, Connector> {
 
     // count the line segments (we don't use many1_count because we want to check consistent style)
     let (mut i, style) = line(i)?;
-    line_ty.set(style).expect("TODO error handling");
+    line_ty.set(style).map_err(mixed)?;
     let mut rank = 1; // we already got one line segment
     while matches!(i.chars().next(), Some('=') | Some('-')) {
         let (i_n, style) = line(i)?;
-        line_ty.set(style).expect("TODO error handling");
+        line_ty.set(style).map_err(mixed)?;
         i = i_n;
         rank += 1;
     }
@@ -312,23 +959,32 @@ fn connector_solid(i: &str) -> IResult<&str, Connector> {
         // if there is no arrow the last line segment does not count towards rank
         rank -= 1;
     }
+    if arrow_start.is_some() && rank > 1 {
+        // the start arrow doesn't take the place of a line segment like the end arrow does, so
+        // take one off to make `<-->` the same length as `-->`
+        rank -= 1;
+    }
 
     Ok((
         i,
         Connector {
-            line_style: line_ty.get().expect("TODO error handling"),
+            line_style: line_ty.get().expect("at least one line segment was parsed"),
             arrow_start,
             arrow_end,
             label: "",
             rank,
+            inline_style: None,
         },
     ))
 }
 
 /// An arrow character.
 ///
-/// `start` is whether we are looking for a left-facing arrow (at the start of a line)
-fn arrow(start: bool) -> impl FnMut(&str) -> IResult<&str, ArrowStyle> {
+/// `start` is whether we are looking for a left-facing arrow (at the start of a line). Circles
+/// (`o`) and crosses (`x`) look the same at either end, so `A o--x B` has a circle at `A` and a
+/// cross at `B`. The marker is only part of the link when it's directly next to the line, so
+/// `A --- oB` links `A` to a node called `oB`.
+fn arrow<'input>(start: bool) -> impl FnMut(Span<'input>) -> IResult<'input, ArrowStyle> {
     move |i| {
         alt((
             value(ArrowStyle::Circle, tag("o")),
@@ -339,7 +995,7 @@ fn arrow(start: bool) -> impl FnMut(&str) -> IResult<&str, ArrowStyle> {
 }
 
 /// A line character (either `=` or `-`)
-fn line(i: &str) -> IResult<&str, LineStyle> {
+fn line(i: Span) -> IResult<LineStyle> {
     alt((
         value(LineStyle::Normal, tag("-")),
         value(LineStyle::Thick, tag("=")),
@@ -347,12 +1003,44 @@ fn line(i: &str) -> IResult<&str, LineStyle> {
 }
 
 /// A node identifier
-fn ident(i: &str) -> IResult<&str, &str> {
-    alphanumeric1(i)
+///
+/// As well as letters and numbers, ids can contain `_`, and `-` or `.` between other characters.
+/// Only allowing `-` when it's followed by a letter or number means ids stop before a link, so
+/// `a-b-->c` is `a-b` linked to `c`.
+fn ident(i: Span) -> IResult<Span> {
+    recognize(pair(
+        ident_word,
+        many0_count(alt((
+            ident_word,
+            recognize(pair(one_of("-."), peek(ident_word))),
+        ))),
+    ))(i)
+}
+
+/// The part of an identifier between separators.
+///
+/// Any unicode letters or numbers are allowed, not just ASCII.
+fn ident_word(i: Span) -> IResult<Span> {
+    alt((take_while1(char::is_alphanumeric), tag("_")))(i)
+}
+
+/// Replace the error from `parser` with one of the given kind, at the position `parser` started
+/// at.
+///
+/// Failures (which stop any backtracking) are passed through unchanged, since they are already
+/// specific.
+fn expect<'input, O>(
+    kind: ErrorKind,
+    mut parser: impl FnMut(Span<'input>) -> IResult<'input, O>,
+) -> impl FnMut(Span<'input>) -> IResult<'input, O> {
+    move |i| match parser(i) {
+        Err(nom::Err::Error(_)) => Err(nom::Err::Error(Error::new(&i, kind.clone()))),
+        other => other,
+    }
 }
 
 /// Whitespace
-fn ws(i: &str) -> IResult<&str, &str> {
+fn ws(i: Span) -> IResult<Span> {
     space0(i)
 }
 
@@ -366,44 +1054,491 @@ impl LineTy {
         LineTy { ty: None }
     }
 
-    fn set(&mut self, ty: LineStyle) -> Result<()> {
+    fn set(&mut self, ty: LineStyle) -> Result<(), ErrorKind> {
         match self.ty.replace(ty) {
             Some(old_ty) if ty == old_ty => Ok(()),
-            Some(_) => Err(anyhow!("mixed - and = in the same connection")),
+            Some(_) => Err(ErrorKind::MixedLineStyle),
             None => Ok(()),
         }
     }
 
-    /// Get the line style
-    ///
-    /// Errors if the line style was never set.
-    fn get(mut self) -> Result<LineStyle> {
-        self.ty
-            .take()
-            .ok_or_else(|| anyhow!("line style was never set"))
+    /// Get the line style, or `None` if it was never set.
+    fn get(mut self) -> Option<LineStyle> {
+        self.ty.take()
     }
 }
 
 /// Keep trying `p` until we get a match, then return all the input before the match and the result
 /// of the parse.
+///
+/// If `p` doesn't match anywhere, the error from trying it at the end of the input is returned.
 fn input_until<I: nom::InputLength + nom::InputTake, O, E>(
     mut p: impl nom::Parser<I, O, E>,
-) -> impl FnMut(I) -> IResult<I, (I, O), E>
+) -> impl FnMut(I) -> nom::IResult<I, (I, O), E>
 where
-    I: nom::InputLength + nom::InputTake,
+    I: nom::InputLength + nom::InputTake + nom::InputIter,
     E: nom::error::ParseError<I>,
 {
     move |i| {
-        let input_len = i.input_len();
-        for offset in 0..input_len {
+        // only split between characters, so multi-byte characters don't get cut in half
+        for (offset, _) in i.iter_indices() {
             let (i, taken) = i.take_split(offset);
             if let Ok((i, res)) = p.parse(i) {
                 return Ok((i, (taken, res)));
             }
         }
-        Err(nom::Err::Error(E::from_error_kind(
-            i,
-            nom::error::ErrorKind::TakeUntil,
-        )))
+        let (i, taken) = i.take_split(i.input_len());
+        let (i, res) = p.parse(i)?;
+        Ok((i, (taken, res)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::Color;
+
+    #[test]
+    fn definition_upgrades_bare_id() {
+        let flow = parse_flowchart("flowchart TD\nA --> B\nA[Start] --> C").unwrap();
+        assert_eq!(flow.nodes["A"].label, "Start");
+
+        // a bare id after the definition doesn't undo it
+        let flow = parse_flowchart("flowchart TD\nA(Start) --> B\nA --> C").unwrap();
+        assert_eq!(flow.nodes["A"].label, "Start");
+        assert_eq!(flow.nodes["A"].style, NodeStyle::Round);
+    }
+
+    #[test]
+    fn conflicting_definitions() {
+        let err = parse_flowchart("flowchart TD\nA[one] --> B\nA(two) --> C").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::ConflictingNode {
+                id: "A".into(),
+                first: ("one".into(), NodeStyle::Square),
+                second: ("two".into(), NodeStyle::Round),
+            }
+        );
+        let msg = err.to_string();
+        assert!(msg.contains("`one`") && msg.contains("`two`"), "{}", msg);
+
+        // the same definition twice is fine
+        assert!(parse_flowchart("flowchart TD\nA[one] --> B\nA[one] --> C").is_ok());
+    }
+
+    #[test]
+    fn duplicate_edge() {
+        let err = parse_flowchart("flowchart TD\nA-->B\nA-->B").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::DuplicateEdge {
+                from: "A".into(),
+                to: "B".into()
+            }
+        );
+
+        // going back the other way is a different edge
+        assert!(parse_flowchart("flowchart TD\nA-->B\nB-->A").is_ok());
+    }
+
+    #[test]
+    fn asymmetric_node() {
+        let flow = parse_flowchart("flowchart LR\nA>flag text] --> B").unwrap();
+        assert_eq!(flow.nodes["A"].style, NodeStyle::Asymmetric);
+        assert_eq!(flow.nodes["A"].label, "flag text");
+        let conn = flow.graph.edge_weight("A", "B").unwrap();
+        assert_eq!(conn.arrow_end, Some(ArrowStyle::Arrow));
+
+        // the flag can also be the target of a link
+        let flow = parse_flowchart("flowchart LR\nA-->B>flag]").unwrap();
+        assert_eq!(flow.nodes["B"].style, NodeStyle::Asymmetric);
+        assert_eq!(flow.nodes["B"].label, "flag");
+        assert!(flow.graph.contains_edge("A", "B"));
+    }
+
+    #[test]
+    fn shapes_in_node_lists() {
+        let flow = parse_flowchart("flowchart TD\nA[x] & B((y)) --> C{z}").unwrap();
+        let shape = |id: &str| (flow.nodes[id].label, flow.nodes[id].style);
+        assert_eq!(shape("A"), ("x", NodeStyle::Square));
+        assert_eq!(shape("B"), ("y", NodeStyle::Circle));
+        assert_eq!(shape("C"), ("z", NodeStyle::Rhombus));
+        assert_eq!(flow.graph.edge_count(), 2);
+
+        let flow = parse_flowchart("flowchart TD\nA[a] &B{b}&  C((c)) --> D").unwrap();
+        let shape = |id: &str| (flow.nodes[id].label, flow.nodes[id].style);
+        assert_eq!(shape("A"), ("a", NodeStyle::Square));
+        assert_eq!(shape("B"), ("b", NodeStyle::Rhombus));
+        assert_eq!(shape("C"), ("c", NodeStyle::Circle));
+        for id in ["A", "B", "C"] {
+            assert!(flow.graph.contains_edge(id, "D"), "{}", id);
+        }
+        assert_eq!(flow.graph.edge_count(), 3);
+    }
+
+    #[test]
+    fn reversed_directions_round_trip() {
+        for (src, direction) in [
+            ("flowchart RL\nA-->B", Direction::RightLeft),
+            ("flowchart BT\nA-->B", Direction::BottomTop),
+            ("graph RL\nA-->B", Direction::RightLeft),
+            ("graph BT\nA-->B", Direction::BottomTop),
+        ] {
+            let flow = parse_flowchart(src).unwrap();
+            assert_eq!(flow.direction, direction, "{}", src);
+            let written = flow.to_mermaid();
+            assert_eq!(parse_flowchart(&written).unwrap().direction, direction);
+        }
+
+        // and the same in a `direction` statement
+        let flow = parse_flowchart("flowchart TD\ndirection BT\nA-->B").unwrap();
+        assert_eq!(flow.direction, Direction::BottomTop);
+        let flow = parse_flowchart("flowchart TD\ndirection RL\nA-->B").unwrap();
+        assert_eq!(flow.direction, Direction::RightLeft);
+    }
+
+    /// The edges of a flowchart as `(from, to)`, sorted.
+    fn edges<'a>(flow: &Flowchart<'a>) -> Vec<(&'a str, &'a str)> {
+        let mut edges = flow
+            .graph
+            .all_edges()
+            .map(|(from, to, _)| (from, to))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    #[test]
+    fn single_link() {
+        let flow = parse_flowchart("flowchart TD\nA --> B").unwrap();
+        assert_eq!(edges(&flow), [("A", "B")]);
+    }
+
+    #[test]
+    fn chained_links() {
+        let flow = parse_flowchart("flowchart TD\nA --> B -.-> C").unwrap();
+        assert_eq!(edges(&flow), [("A", "B"), ("B", "C")]);
+        let line_style = |from, to| flow.graph.edge_weight(from, to).unwrap().line_style;
+        assert_eq!(line_style("A", "B"), LineStyle::Normal);
+        assert_eq!(line_style("B", "C"), LineStyle::Dotted);
+    }
+
+    #[test]
+    fn node_lists_on_both_sides() {
+        let flow = parse_flowchart("flowchart TD\nA & B -->|go| C & D").unwrap();
+        assert_eq!(
+            edges(&flow),
+            [("A", "C"), ("A", "D"), ("B", "C"), ("B", "D")]
+        );
+        // every edge gets the same connector
+        for (_, _, conn) in flow.graph.all_edges() {
+            assert_eq!(conn.label, "go");
+            assert_eq!(conn.arrow_end, Some(ArrowStyle::Arrow));
+        }
+    }
+
+    #[test]
+    fn node_lists_in_chains() {
+        // fan out then in
+        let flow = parse_flowchart("flowchart TD\nA --> B & C --> D").unwrap();
+        assert_eq!(
+            edges(&flow),
+            [("A", "B"), ("A", "C"), ("B", "D"), ("C", "D")]
+        );
+
+        // lists at every step
+        let flow = parse_flowchart("flowchart TD\nA & B --> C & D ==> E & F").unwrap();
+        assert_eq!(
+            edges(&flow),
+            [
+                ("A", "C"),
+                ("A", "D"),
+                ("B", "C"),
+                ("B", "D"),
+                ("C", "E"),
+                ("C", "F"),
+                ("D", "E"),
+                ("D", "F"),
+            ]
+        );
+        for (from, _, conn) in flow.graph.all_edges() {
+            let expected = if from == "C" || from == "D" {
+                LineStyle::Thick
+            } else {
+                LineStyle::Normal
+            };
+            assert_eq!(conn.line_style, expected);
+        }
+    }
+
+    #[test]
+    fn style_statement_sets_inline_style() {
+        let flow = parse_flowchart(
+            "flowchart LR\nA --> B\nstyle A fill:#f9f,stroke:#333,stroke-width:4px,color:#fff",
+        )
+        .unwrap();
+        let style = flow.nodes["A"].inline_style.unwrap();
+        assert_eq!(style.fill, Some(Color::rgb8(0xff, 0x99, 0xff)));
+        assert_eq!(style.stroke, Some(Color::rgb8(0x33, 0x33, 0x33)));
+        assert_eq!(style.stroke_width, Some(4.));
+        assert_eq!(style.color, Some(Color::WHITE));
+        assert_eq!(flow.nodes["B"].inline_style, None);
+
+        // the statement can come before the node is defined, and later statements add to it
+        let flow =
+            parse_flowchart("flowchart LR\nstyle A fill:#f9f\nstyle A stroke:#333\nA[Start]")
+                .unwrap();
+        assert_eq!(flow.nodes["A"].label, "Start");
+        let style = flow.nodes["A"].inline_style.unwrap();
+        assert_eq!(style.fill, Some(Color::rgb8(0xff, 0x99, 0xff)));
+        assert_eq!(style.stroke, Some(Color::rgb8(0x33, 0x33, 0x33)));
+    }
+
+    #[test]
+    fn style_statement_ignores_unknown() {
+        let flow = parse_flowchart(
+            "flowchart LR\nA --> B\nstyle A fill:#f9f,font-family:serif,stroke:notacolor,bogus",
+        )
+        .unwrap();
+        assert_eq!(
+            flow.nodes["A"].inline_style,
+            Some(NodeInlineStyle {
+                fill: Some(Color::rgb8(0xff, 0x99, 0xff)),
+                ..NodeInlineStyle::default()
+            })
+        );
+
+        // nothing we understand still gives an (empty) style
+        let flow = parse_flowchart("flowchart LR\nA --> B\nstyle B opacity:0.5").unwrap();
+        assert_eq!(
+            flow.nodes["B"].inline_style,
+            Some(NodeInlineStyle::default())
+        );
+    }
+
+    #[test]
+    fn standalone_node_definition() {
+        let flow = parse_flowchart("flowchart LR\nA[Start]\nB & C((end))").unwrap();
+        assert_eq!(flow.nodes.len(), 3);
+        assert_eq!(flow.nodes["A"].label, "Start");
+        assert_eq!(flow.nodes["C"].style, NodeStyle::Circle);
+        assert_eq!(flow.graph.edge_count(), 0);
+    }
+
+    #[test]
+    fn pipe_labels_on_every_line_style() {
+        let cases = [
+            (
+                "A ==>|yes| B",
+                "yes",
+                LineStyle::Thick,
+                Some(ArrowStyle::Arrow),
+            ),
+            (
+                "A -.->|no| B",
+                "no",
+                LineStyle::Dotted,
+                Some(ArrowStyle::Arrow),
+            ),
+            ("A ---|maybe| B", "maybe", LineStyle::Normal, None),
+            (
+                "A -->|sure| B",
+                "sure",
+                LineStyle::Normal,
+                Some(ArrowStyle::Arrow),
+            ),
+            ("A ===|thick| B", "thick", LineStyle::Thick, None),
+            ("A -.-|dots| B", "dots", LineStyle::Dotted, None),
+        ];
+        for (line, label, line_style, arrow_end) in cases {
+            let src = format!("flowchart LR\n{}", line);
+            let flow = parse_flowchart(&src).unwrap();
+            let conn = flow.graph.edge_weight("A", "B").unwrap();
+            assert_eq!(conn.label, label, "{}", line);
+            assert_eq!(conn.line_style, line_style, "{}", line);
+            assert_eq!(conn.arrow_start, None, "{}", line);
+            assert_eq!(conn.arrow_end, arrow_end, "{}", line);
+        }
+    }
+
+    #[test]
+    fn statement_on_header_line() {
+        let flow = parse_flowchart("flowchart LR A-->B").unwrap();
+        assert_eq!(flow.direction, Direction::LeftRight);
+        assert_eq!(edges(&flow), [("A", "B")]);
+
+        // later lines still work
+        let flow = parse_flowchart("graph TD A-->B\n  B-->C").unwrap();
+        assert_eq!(edges(&flow), [("A", "B"), ("B", "C")]);
+
+        // the direction has to be separated from the statement
+        let err = parse_flowchart("flowchart LRA-->B").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnknownDirection("LRA".into()));
+        assert_eq!((err.line, err.col), (1, 11));
+    }
+
+    #[test]
+    fn pipe_labels_are_opaque() {
+        let label = |src: &str| {
+            let flow = parse_flowchart(src).unwrap();
+            let label = flow.graph.edge_weight("A", "B").unwrap().label;
+            label.to_string()
+        };
+        assert_eq!(
+            label("flowchart LR\nA -->|\"weird: [text]\"| B"),
+            "weird: [text]"
+        );
+        // brackets etc. don't need quoting, and surrounding space is dropped
+        assert_eq!(
+            label("flowchart LR\nA -->| f(x): {y} [z] | B"),
+            "f(x): {y} [z]"
+        );
+        assert_eq!(label("flowchart LR\nA -->|a --> b| B"), "a --> b");
+        // quoting allows a `|` in the label
+        assert_eq!(label("flowchart LR\nA -->|\"a|b\"| B"), "a|b");
+    }
+
+    #[test]
+    fn id_characters() {
+        let id = |i| {
+            let (rest, id) = ident(Span::new(i)).unwrap();
+            (*id.fragment(), *rest.fragment())
+        };
+        assert_eq!(id("node_1 rest"), ("node_1", " rest"));
+        assert_eq!(id("_private"), ("_private", ""));
+        assert_eq!(id("api.v2"), ("api.v2", ""));
+        assert_eq!(id("a-b"), ("a-b", ""));
+        // separators at the end aren't part of the id
+        assert_eq!(id("a-->b"), ("a", "-->b"));
+        assert_eq!(id("a.-.->b"), ("a", ".-.->b"));
+        assert_eq!(id("end."), ("end", "."));
+
+        let flow = parse_flowchart("flowchart LR\na-b --> c\nnode_1-->api.v2\nx-y-.->z").unwrap();
+        assert_eq!(
+            edges(&flow),
+            [("a-b", "c"), ("node_1", "api.v2"), ("x-y", "z")]
+        );
+        let conn = flow.graph.edge_weight("x-y", "z").unwrap();
+        assert_eq!(conn.line_style, LineStyle::Dotted);
+    }
+
+    #[test]
+    fn unicode() {
+        let flow = parse_flowchart(
+            "flowchart LR\nノード1[\"開始 🚀\"] --> Узел_2(Привет)\nÉtape --> ノード1",
+        )
+        .unwrap();
+        assert_eq!(flow.nodes["ノード1"].label, "開始 🚀");
+        assert_eq!(flow.nodes["Узел_2"].label, "Привет");
+        assert_eq!(flow.nodes["Узел_2"].style, NodeStyle::Round);
+        assert!(flow.graph.contains_edge("ノード1", "Узел_2"));
+        assert!(flow.graph.contains_edge("Étape", "ノード1"));
+
+        // error columns count characters, not bytes
+        let err = parse_flowchart("flowchart LR\nノード1 --> ?").unwrap_err();
+        assert_eq!((err.line, err.col), (2, 10));
+    }
+
+    #[test]
+    fn longest_shape_delimiters_win() {
+        let cases = [
+            (r"A[[x]]", NodeStyle::Subroutine, "x"),
+            (r"A(((x)))", NodeStyle::DoubleCircle, "x"),
+            (r"A((x))", NodeStyle::Circle, "x"),
+            (r"A([x])", NodeStyle::Stadium, "x"),
+            (r"A[(x)]", NodeStyle::Cylinder, "x"),
+            (r"A{{x}}", NodeStyle::Hexagon, "x"),
+            (r"A[/x/]", NodeStyle::Parallelogram, "x"),
+            (r"A[\x\]", NodeStyle::ParallelogramRev, "x"),
+            (r"A[/x\]", NodeStyle::Trapezoid, "x"),
+            (r"A[\x/]", NodeStyle::TrapezoidRev, "x"),
+            (r"A[x]", NodeStyle::Square, "x"),
+            (r"A(x)", NodeStyle::Round, "x"),
+            (r"A{x}", NodeStyle::Rhombus, "x"),
+            // when the longer shape doesn't close, a shorter one is tried
+            (r"A[/path]", NodeStyle::Square, "/path"),
+            (r"A((x)", NodeStyle::Round, "(x"),
+        ];
+        for (node_src, style, label) in cases {
+            let src = format!("flowchart LR\n{} --> B", node_src);
+            let flow = parse_flowchart(&src).unwrap();
+            assert_eq!(flow.nodes["A"].style, style, "{}", node_src);
+            assert_eq!(flow.nodes["A"].label, label, "{}", node_src);
+        }
+    }
+
+    #[test]
+    fn link_style_one_edge() {
+        let flow = parse_flowchart(
+            "flowchart LR\nA --> B\nB --> C\nC --> A\nlinkStyle 0 stroke:#ff3,stroke-width:4px",
+        )
+        .unwrap();
+        let style = |from, to| flow.graph.edge_weight(from, to).unwrap().inline_style;
+        assert_eq!(
+            style("A", "B"),
+            Some(EdgeInlineStyle {
+                stroke: Some(Color::rgb8(0xff, 0xff, 0x33)),
+                stroke_width: Some(4.),
+                dash: None,
+            })
+        );
+        assert_eq!(style("B", "C"), None);
+        assert_eq!(style("C", "A"), None);
+    }
+
+    #[test]
+    fn link_style_default() {
+        let flow = parse_flowchart(
+            "flowchart LR\nA --> B --> C\nC --> D\n\
+             linkStyle default stroke:#333,stroke-width:2px\n\
+             linkStyle 1,2 stroke:#f00,stroke-dasharray:5\n\
+             linkStyle 9 stroke:#0f0",
+        )
+        .unwrap();
+        let style = |from, to| {
+            flow.graph
+                .edge_weight(from, to)
+                .unwrap()
+                .inline_style
+                .unwrap()
+        };
+        let grey = Some(Color::rgb8(0x33, 0x33, 0x33));
+        let red = Some(Color::rgb8(0xff, 0, 0));
+        // edges are numbered in the order they're defined, including in chains
+        assert_eq!(style("A", "B").stroke, grey);
+        assert_eq!(style("A", "B").dash, None);
+        // particular styles win, but the default fills in the rest
+        for (from, to) in [("B", "C"), ("C", "D")] {
+            assert_eq!(style(from, to).stroke, red);
+            assert_eq!(style(from, to).stroke_width, Some(2.));
+            assert_eq!(style(from, to).dash, Some([5., 5.]));
+        }
+    }
+
+    #[test]
+    fn direction_statement() {
+        let flow = parse_flowchart("flowchart TD\nA --> B\ndirection LR\nB --> C").unwrap();
+        assert_eq!(flow.direction, Direction::LeftRight);
+        assert_eq!(edges(&flow), [("A", "B"), ("B", "C")]);
+
+        // the last one wins
+        let flow = parse_flowchart("graph TB\ndirection RL\n  direction BT").unwrap();
+        assert_eq!(flow.direction, Direction::BottomTop);
+
+        let err = parse_flowchart("flowchart TD\ndirection XY").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnknownDirection("XY".into()));
+        assert_eq!((err.line, err.col), (2, 11));
+        assert_eq!(
+            err.kind().to_string(),
+            "unknown direction `XY`, expected one of `TB` (or `TD`), `BT`, `LR` or `RL`"
+        );
+
+        // on its own, `direction` is just a node
+        let flow = parse_flowchart("flowchart TD\ndirection").unwrap();
+        assert_eq!(flow.direction, Direction::TopBottom);
+        assert!(flow.nodes.contains_key("direction"));
     }
 }
@@ -0,0 +1,70 @@
+use super::Flowchart;
+use petgraph::{algo, Direction as EdgeDirection};
+use std::{error::Error, fmt};
+
+impl<'input> Flowchart<'input> {
+    /// Get the ids of the nodes that `id` has an edge to.
+    ///
+    /// If there is no node with the given id, there are no neighbors.
+    pub fn neighbors(&self, id: &str) -> impl Iterator<Item = &'input str> + '_ {
+        // use the id borrowed from the input, so the neighbors can be too
+        let id = self.nodes.get_key_value(id).map(|(id, _)| *id);
+        id.into_iter()
+            .flat_map(|id| self.graph.neighbors_directed(id, EdgeDirection::Outgoing))
+    }
+
+    /// Whether it's possible to get from the node `from` to the node `to` by following edges.
+    ///
+    /// A node always has a path to itself.
+    pub fn has_path(&self, from: &str, to: &str) -> bool {
+        from == to || algo::has_path_connecting(&self.graph, from, to, None)
+    }
+
+    /// Get the ids of all the nodes, ordered so that every edge goes from a node earlier in the
+    /// list to one later in the list.
+    ///
+    /// Nodes without any edges come at the end, sorted by id. If the flowchart contains a cycle
+    /// there is no such order, and the nodes making up the cycle are returned in the error.
+    pub fn topological_order(&self) -> Result<Vec<&'input str>, CycleError<'input>> {
+        let mut order = algo::toposort(&self.graph, None).map_err(|cycle| {
+            let node = cycle.node_id();
+            let nodes = algo::tarjan_scc(&self.graph)
+                .into_iter()
+                .find(|component| component.contains(&node))
+                .unwrap_or_else(|| vec![node]);
+            CycleError { nodes }
+        })?;
+
+        let mut unconnected = self
+            .nodes
+            .keys()
+            .filter(|id| !self.graph.contains_node(id))
+            .copied()
+            .collect::<Vec<_>>();
+        unconnected.sort_unstable();
+        order.extend(unconnected);
+        Ok(order)
+    }
+}
+
+/// The error returned by [`Flowchart::topological_order`] when the flowchart has a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError<'input> {
+    /// The ids of the nodes that are part of the cycle.
+    pub nodes: Vec<&'input str>,
+}
+
+impl fmt::Display for CycleError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the flowchart contains a cycle through nodes ")?;
+        for (idx, id) in self.nodes.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "`{}`", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CycleError<'_> {}
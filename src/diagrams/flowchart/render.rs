@@ -0,0 +1,459 @@
+use super::{
+    ArrowStyle, Direction, EdgeLayout, Flowchart, FlowchartLayout, FlowchartStyle, NodeStyle,
+};
+#[cfg(feature = "svg")]
+use crate::diagrams::svg::Tooltip;
+use crate::{
+    style::{clear_background, rich_text_layout, TextMeasurer},
+    text::{label_text, RichText},
+};
+use kurbo::{
+    Affine, Arc, BezPath, Circle, Ellipse, Line, Point, Rect, RoundedRect, Shape, Size, Vec2,
+};
+use piet::{RenderContext, TextLayout};
+use std::{collections::HashMap, f64::consts::PI};
+
+/// The accuracy to use when converting curves to bezier paths.
+const TOLERANCE: f64 = 0.1;
+/// The corner radius of [`NodeStyle::Round`] nodes.
+const ROUND_RADIUS: f64 = 5.;
+/// The gap between the side bars of a [`NodeStyle::Subroutine`] node and its outline.
+const SUBROUTINE_INSET: f64 = 8.;
+/// The gap between the two circles of a [`NodeStyle::DoubleCircle`] node.
+const DOUBLE_CIRCLE_GAP: f64 = 5.;
+/// The length of an arrowhead, along the edge.
+const ARROW_LENGTH: f64 = 8.;
+/// The space between an edge label and the edge of its background.
+const EDGE_LABEL_PADDING: f64 = 2.;
+
+/// Everything needed to draw a chart, once text has been measured and the chart laid out.
+struct Drawing<'input> {
+    layout: FlowchartLayout<'input>,
+    /// The outline of each node, with its label centered on the origin.
+    nodes: HashMap<&'input str, BezPath>,
+}
+
+impl<'input> Drawing<'input> {
+    /// Measure and lay out `chart`, flowing in `direction`.
+    fn new(
+        chart: &Flowchart<'input>,
+        direction: Direction,
+        style: &FlowchartStyle,
+        text: &mut dyn TextMeasurer,
+    ) -> Result<Self, piet::Error> {
+        let mut nodes = HashMap::new();
+        for node in chart.nodes.values() {
+            let label_size = text.measure_rich(&node.rich_label(), &style.node_label(node))?;
+            // center the label on the origin, and put the outline round it
+            let padding = Size::new(style.node_padding, style.node_padding) * 2.;
+            let rect = Rect::from_center_size(Point::ORIGIN, label_size + padding);
+            nodes.insert(node.id, node_shape(node.style, rect));
+        }
+        let layout = chart.layout(direction, |node| nodes[node.id].bounding_box().size());
+        Ok(Drawing { layout, nodes })
+    }
+
+    /// The transform that moves the node with the given id from the origin (where its label is
+    /// centered) to where it is in the layout.
+    fn node_transform(&self, id: &str) -> Affine {
+        let bbox = self.nodes[id].bounding_box();
+        Affine::translate(self.layout.nodes[id].rect.center() - bbox.center())
+    }
+
+    /// The outline of the node with the given id, where it is in the layout.
+    fn outline(&self, id: &str) -> BezPath {
+        self.node_transform(id) * self.nodes[id].clone()
+    }
+}
+
+/// Render the whole chart flowing in `direction`, including the background.
+pub(super) fn render<RC: RenderContext>(
+    chart: &Flowchart,
+    direction: Direction,
+    style: &FlowchartStyle,
+    ctx: &mut RC,
+    text: &mut RC::Text,
+) -> Result<(), piet::Error> {
+    clear_background(ctx, style.background_color);
+    let drawing = Drawing::new(chart, direction, style, text)?;
+    ctx.with_save(|ctx| {
+        ctx.transform(Affine::translate((style.margin, style.margin)));
+        let outlines = drawing
+            .nodes
+            .keys()
+            .map(|&id| (id, drawing.outline(id)))
+            .collect::<HashMap<_, _>>();
+
+        // edges go underneath the nodes they connect
+        for ((_, _, conn), edge) in chart.graph.all_edges().zip(&drawing.layout.edges) {
+            let stroke = style.edge_stroke(conn);
+            let brush = ctx.solid_brush(stroke.color);
+            let mut points = edge.points.clone();
+            let last = points.len() - 1;
+            if edge.from != edge.to {
+                points[0] = boundary_point(&outlines[edge.from], points[0], points[1]);
+                points[last] = boundary_point(&outlines[edge.to], points[last], points[last - 1]);
+            }
+            if let Some(arrow) = conn.arrow_start {
+                let direction = points[0] - points[1];
+                points[0] = draw_arrowhead(ctx, arrow, points[0], direction, &brush, stroke.width);
+            }
+            if let Some(arrow) = conn.arrow_end {
+                let direction = points[last] - points[last - 1];
+                points[last] =
+                    draw_arrowhead(ctx, arrow, points[last], direction, &brush, stroke.width);
+            }
+            let edge = EdgeLayout {
+                points,
+                ..edge.clone()
+            };
+            ctx.stroke_styled(edge.path(), &brush, stroke.width, &stroke.piet_style());
+        }
+
+        // edge labels go over all edges, in case another edge passes underneath
+        for ((_, _, conn), edge) in chart.graph.all_edges().zip(&drawing.layout.edges) {
+            if conn.label.is_empty() {
+                continue;
+            }
+            let label = RichText::from_label(&label_text(conn.label));
+            let label = rich_text_layout(text, &label, &style.edge_label)?;
+            let rect = edge_label_rect(edge, label.size());
+            ctx.fill(rect, &style.edge_label_background);
+            ctx.draw_text(
+                &label,
+                rect.origin() + Vec2::new(EDGE_LABEL_PADDING, EDGE_LABEL_PADDING),
+            );
+        }
+
+        for node in chart.nodes.values() {
+            let label = rich_text_layout(text, &node.rich_label(), &style.node_label(node))?;
+            let outline = &outlines[node.id];
+            let stroke = style.node_outline(node);
+            ctx.fill(outline, &style.node_fill(node));
+            ctx.stroke_styled(outline, &stroke.color, stroke.width, &stroke.piet_style());
+            let center = drawing.node_transform(node.id) * Point::ORIGIN;
+            ctx.draw_text(&label, center - label.size().to_vec2() * 0.5);
+        }
+        Ok(())
+    })
+}
+
+/// Get the size of the chart, including the margin.
+pub(super) fn size(
+    chart: &Flowchart,
+    style: &FlowchartStyle,
+    text: &mut dyn TextMeasurer,
+) -> Result<Size, piet::Error> {
+    let drawing = Drawing::new(chart, chart.direction, style, text)?;
+    let margin = Size::new(style.margin, style.margin) * 2.;
+    Ok(drawing.layout.size + margin)
+}
+
+/// Get the outline of each node and the area of each edge label, along with the text they show,
+/// in the coordinates used by [`render`].
+///
+/// Node outlines are given the id of the node they are for.
+#[cfg(feature = "svg")]
+pub(super) fn tooltips(
+    chart: &Flowchart,
+    style: &FlowchartStyle,
+    text: &mut dyn TextMeasurer,
+) -> Result<Vec<Tooltip>, piet::Error> {
+    let drawing = Drawing::new(chart, chart.direction, style, text)?;
+    let margin = Affine::translate((style.margin, style.margin));
+    // in a stable order, so the output doesn't depend on hashmap order
+    let mut nodes = chart.nodes.values().collect::<Vec<_>>();
+    nodes.sort_unstable_by_key(|node| node.id);
+
+    let mut tooltips = vec![];
+    for node in nodes {
+        tooltips.push(Tooltip {
+            shape: margin * drawing.outline(node.id),
+            text: node.rich_label().plain_text(),
+            id: Some(format!("node-{}", node.id)),
+        });
+    }
+    for ((_, _, conn), edge) in chart.graph.all_edges().zip(&drawing.layout.edges) {
+        if conn.label.is_empty() {
+            continue;
+        }
+        let label = RichText::from_label(&label_text(conn.label));
+        let rect = edge_label_rect(edge, text.measure_rich(&label, &style.edge_label)?);
+        tooltips.push(Tooltip {
+            shape: margin * rect.to_path(TOLERANCE),
+            text: label.plain_text(),
+            id: None,
+        });
+    }
+    Ok(tooltips)
+}
+
+/// The area of an edge label and its background, given the size of the label.
+fn edge_label_rect(edge: &EdgeLayout, label_size: Size) -> Rect {
+    Rect::from_center_size(label_position(edge), label_size)
+        .inflate(EDGE_LABEL_PADDING, EDGE_LABEL_PADDING)
+}
+
+/// Where to put the label of an edge: halfway along its route.
+fn label_position(edge: &EdgeLayout) -> Point {
+    let points = &edge.points;
+    if points.len() % 2 == 1 {
+        points[points.len() / 2]
+    } else {
+        points[points.len() / 2 - 1].midpoint(points[points.len() / 2])
+    }
+}
+
+/// Get the outline of a node with the given style.
+///
+/// `rect` is the area taken up by the label: the outline is built around it so the label always
+/// fits inside. Some shapes (like the cylinder and the subroutine) contain more than one subpath,
+/// so the outline should be stroked as well as filled.
+pub fn node_shape(style: NodeStyle, rect: Rect) -> BezPath {
+    let center = rect.center();
+    let (width, height) = (rect.width(), rect.height());
+    // how far the slanted sides of some shapes stick out past the label
+    let slant = height * 0.5;
+    match style {
+        NodeStyle::Square => rect.to_path(TOLERANCE),
+        NodeStyle::Round => {
+            RoundedRect::from_rect(rect, ROUND_RADIUS.min(height * 0.5)).to_path(TOLERANCE)
+        }
+        NodeStyle::Stadium => {
+            // the ends are semicircles, which stick out past the label
+            RoundedRect::from_rect(rect.inflate(slant, 0.), slant).to_path(TOLERANCE)
+        }
+        NodeStyle::Subroutine => {
+            let outer = rect.inflate(SUBROUTINE_INSET, 0.);
+            let mut path = outer.to_path(TOLERANCE);
+            for x in [rect.x0, rect.x1] {
+                path.move_to((x, rect.y0));
+                path.line_to((x, rect.y1));
+            }
+            path
+        }
+        NodeStyle::Cylinder => {
+            // the same proportions as mermaid.js uses
+            let rx = width * 0.5;
+            let ry = rx / (2.5 + width / 50.);
+            let (top, bottom) = (rect.y0 - ry, rect.y1 + ry);
+
+            // the sides and the front of the base
+            let mut path = BezPath::new();
+            path.move_to((rect.x0, top));
+            path.line_to((rect.x0, bottom));
+            let base = Arc {
+                center: Point::new(center.x, bottom),
+                radii: (rx, ry).into(),
+                start_angle: PI,
+                sweep_angle: -PI,
+                x_rotation: 0.,
+            };
+            path.extend(base.append_iter(TOLERANCE));
+            path.line_to((rect.x1, top));
+            // the whole of the lid is visible
+            let lid = Ellipse::new((center.x, top), (rx, ry), 0.);
+            path.extend(lid.path_elements(TOLERANCE));
+            path
+        }
+        NodeStyle::Circle => Circle::new(center, half_diagonal(rect)).to_path(TOLERANCE),
+        NodeStyle::DoubleCircle => {
+            let radius = half_diagonal(rect);
+            let mut path = Circle::new(center, radius).to_path(TOLERANCE);
+            path.extend(Circle::new(center, radius + DOUBLE_CIRCLE_GAP).path_elements(TOLERANCE));
+            path
+        }
+        NodeStyle::Asymmetric => polygon(&[
+            Point::new(rect.x0 - slant, rect.y0),
+            Point::new(rect.x1, rect.y0),
+            Point::new(rect.x1, rect.y1),
+            Point::new(rect.x0 - slant, rect.y1),
+            Point::new(rect.x0, center.y),
+        ]),
+        NodeStyle::Rhombus => {
+            // a diamond with twice the width and height of the label touches its corners
+            polygon(&[
+                Point::new(center.x, center.y - height),
+                Point::new(center.x + width, center.y),
+                Point::new(center.x, center.y + height),
+                Point::new(center.x - width, center.y),
+            ])
+        }
+        NodeStyle::Hexagon => polygon(&[
+            Point::new(rect.x0, rect.y0),
+            Point::new(rect.x1, rect.y0),
+            Point::new(rect.x1 + slant, center.y),
+            Point::new(rect.x1, rect.y1),
+            Point::new(rect.x0, rect.y1),
+            Point::new(rect.x0 - slant, center.y),
+        ]),
+        NodeStyle::Parallelogram => polygon(&[
+            Point::new(rect.x0, rect.y0),
+            Point::new(rect.x1 + slant, rect.y0),
+            Point::new(rect.x1, rect.y1),
+            Point::new(rect.x0 - slant, rect.y1),
+        ]),
+        NodeStyle::ParallelogramRev => polygon(&[
+            Point::new(rect.x0 - slant, rect.y0),
+            Point::new(rect.x1, rect.y0),
+            Point::new(rect.x1 + slant, rect.y1),
+            Point::new(rect.x0, rect.y1),
+        ]),
+        NodeStyle::Trapezoid => polygon(&[
+            Point::new(rect.x0, rect.y0),
+            Point::new(rect.x1, rect.y0),
+            Point::new(rect.x1 + slant, rect.y1),
+            Point::new(rect.x0 - slant, rect.y1),
+        ]),
+        NodeStyle::TrapezoidRev => polygon(&[
+            Point::new(rect.x0 - slant, rect.y0),
+            Point::new(rect.x1 + slant, rect.y0),
+            Point::new(rect.x1, rect.y1),
+            Point::new(rect.x0, rect.y1),
+        ]),
+    }
+}
+
+/// Find where a line from `center` towards `toward` leaves `outline`.
+///
+/// This is where an edge should end, so that its arrowhead touches the node rather than being
+/// hidden underneath it. If the line doesn't cross the outline, `center` is returned.
+pub fn boundary_point(outline: &BezPath, center: Point, toward: Point) -> Point {
+    let line = Line::new(center, toward);
+    outline
+        .segments()
+        .flat_map(|seg| seg.intersect_line(line))
+        // the outermost crossing, for shapes with more than one subpath
+        .map(|hit| hit.line_t)
+        .reduce(f64::max)
+        .map(|t| line.p0.lerp(line.p1, t))
+        .unwrap_or(center)
+}
+
+/// Draw the marker for an end of an edge.
+///
+/// `tip` is where the edge meets the node (see [`boundary_point`]) and `direction` is the
+/// direction the edge is travelling in as it arrives there. For an arrow at the start of an edge,
+/// pass the start point and the direction pointing back towards it. `width` is the line width used
+/// for [`ArrowStyle::Cross`].
+///
+/// Returns the point the edge line should stop at so it doesn't poke through the marker.
+pub fn draw_arrowhead<RC: RenderContext>(
+    ctx: &mut RC,
+    style: ArrowStyle,
+    tip: Point,
+    direction: Vec2,
+    brush: &RC::Brush,
+    width: f64,
+) -> Point {
+    if direction.hypot() == 0. {
+        // there's no sensible way to point the marker
+        return tip;
+    }
+    let along = direction.normalize() * ARROW_LENGTH;
+    // perpendicular to the edge, half the width of the marker
+    let across = Vec2::new(-along.y, along.x) * 0.5;
+    let base = tip - along;
+    match style {
+        ArrowStyle::Arrow => {
+            ctx.fill(polygon(&[tip, base + across, base - across]), brush);
+        }
+        ArrowStyle::Circle => {
+            let radius = ARROW_LENGTH * 0.5;
+            ctx.fill(Circle::new(tip - along * 0.5, radius), brush);
+        }
+        ArrowStyle::Cross => {
+            let center = tip - along * 0.5;
+            let (a, b) = ((along + across * 2.) * 0.5, (along - across * 2.) * 0.5);
+            ctx.stroke(Line::new(center - a, center + a), brush, width);
+            ctx.stroke(Line::new(center - b, center + b), brush, width);
+        }
+    }
+    base
+}
+
+/// The radius of the smallest circle containing `rect`.
+fn half_diagonal(rect: Rect) -> f64 {
+    rect.width().hypot(rect.height()) * 0.5
+}
+
+/// A closed path joining up `points`.
+fn polygon(points: &[Point]) -> BezPath {
+    let mut path = BezPath::new();
+    path.move_to(points[0]);
+    for &point in &points[1..] {
+        path.line_to(point);
+    }
+    path.close_path();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Recorder;
+
+    #[test]
+    fn asymmetric_shape() {
+        let rect = Rect::new(0., 0., 100., 20.);
+        let shape = node_shape(NodeStyle::Asymmetric, rect);
+        // the label fits inside
+        for corner in [(0.5, 0.5), (99.5, 0.5), (99.5, 19.5), (0.5, 19.5)] {
+            assert!(shape.contains(corner.into()), "{:?}", corner);
+        }
+        // the notch is cut into the left side, and the right side is flat
+        assert!(!shape.contains(Point::new(-5., 10.)));
+        assert!(shape.contains(Point::new(-5., 1.)));
+        assert!(shape.bounding_box().x0 < rect.x0);
+        assert_eq!(shape.bounding_box().x1, rect.x1);
+    }
+
+    #[test]
+    fn edge_label_background() {
+        let edge = EdgeLayout {
+            from: "A",
+            to: "B",
+            points: vec![
+                Point::new(0., 0.),
+                Point::new(0., 50.),
+                Point::new(100., 50.),
+            ],
+            curved: false,
+        };
+        // centered on the middle point, with padding all round
+        let rect = edge_label_rect(&edge, Size::new(30., 10.));
+        assert_eq!(rect.center(), Point::new(0., 50.));
+        assert_eq!(
+            rect.size(),
+            Size::new(30. + 2. * EDGE_LABEL_PADDING, 10. + 2. * EDGE_LABEL_PADDING)
+        );
+        // between the two middle points when there are an even number
+        let edge = EdgeLayout {
+            points: vec![Point::new(0., 0.), Point::new(100., 0.)],
+            ..edge
+        };
+        assert_eq!(
+            edge_label_rect(&edge, Size::ZERO).center(),
+            Point::new(50., 0.)
+        );
+    }
+
+    #[test]
+    fn multiline_edge_label() {
+        let chart = Flowchart::parse("flowchart LR\nA -->|first<br>second| B").unwrap();
+        let mut rc = Recorder::with_char_size(10.);
+        chart.render(&mut rc).unwrap();
+        let (_, label) = rc
+            .text_rects()
+            .into_iter()
+            .find(|(text, _)| *text == "first\nsecond")
+            .unwrap();
+        // both lines are laid out, as wide as the longer one
+        assert_eq!(label.size(), Size::new(60., 20.));
+        let background = label.inflate(EDGE_LABEL_PADDING, EDGE_LABEL_PADDING);
+        assert!(rc
+            .fills()
+            .iter()
+            .any(|(shape, _)| shape.bounding_box() == background));
+    }
+}
@@ -0,0 +1,114 @@
+//! `serde` support for [`Flowchart`].
+//!
+//! The graph is stored as a list of nodes and a list of edges, rather than using petgraph's own
+//! representation.
+
+use super::{Connector, Direction, Flowchart, Node};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+struct FlowchartRepr<'input> {
+    direction: Direction,
+    #[serde(borrow)]
+    nodes: Vec<Node<'input>>,
+    #[serde(borrow)]
+    edges: Vec<EdgeRepr<'input>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EdgeRepr<'input> {
+    from: &'input str,
+    to: &'input str,
+    #[serde(borrow)]
+    conn: Connector<'input>,
+}
+
+impl Serialize for Flowchart<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes = self.nodes.values().copied().collect::<Vec<_>>();
+        // sort so the output is deterministic
+        nodes.sort_by_key(|node| node.id);
+        let edges = self
+            .graph
+            .all_edges()
+            .map(|(from, to, conn)| EdgeRepr {
+                from,
+                to,
+                conn: *conn,
+            })
+            .collect();
+        FlowchartRepr {
+            direction: self.direction,
+            nodes,
+            edges,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de: 'input, 'input> Deserialize<'de> for Flowchart<'input> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = FlowchartRepr::deserialize(deserializer)?;
+        let mut chart = Flowchart::new(repr.direction);
+        for node in repr.nodes {
+            chart.nodes.insert(node.id, node);
+        }
+        for edge in repr.edges {
+            for id in [edge.from, edge.to] {
+                if !chart.nodes.contains_key(id) {
+                    return Err(de::Error::custom(format!(
+                        "edge refers to unknown node {:?}",
+                        id
+                    )));
+                }
+            }
+            chart.graph.add_edge(edge.from, edge.to, edge.conn);
+        }
+        Ok(chart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flowchart::FlowchartOwned;
+
+    const SRC: &str =
+        "flowchart LR\n  A[Start] -->|go| B{Choose}\n  B -.-> C((End))\n  style C fill:#f9f";
+
+    #[test]
+    fn json_round_trip() {
+        let chart = Flowchart::parse(SRC).unwrap();
+        let json = serde_json::to_string(&chart).unwrap();
+        let back: Flowchart = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.direction, chart.direction);
+        assert!(chart.diff(&back).is_empty(), "{:?}", chart.diff(&back));
+        // the output doesn't depend on hashmap order
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+    }
+
+    #[test]
+    fn owned_json_round_trip() {
+        // escaped strings can't be borrowed from the json, so need the owned form
+        let chart = Flowchart::parse("flowchart TD\n  A[\"say \\\"hi\\\"\"] --> B").unwrap();
+        let json = serde_json::to_string(&chart.clone().into_owned()).unwrap();
+        let back: FlowchartOwned = serde_json::from_str(&json).unwrap();
+        let back = back.as_flowchart();
+        assert_eq!(back.nodes["A"].label, chart.nodes["A"].label);
+        assert!(chart.diff(&back).is_empty());
+    }
+
+    #[test]
+    fn unknown_node() {
+        let json = r#"{
+            "direction": "TopBottom",
+            "nodes": [],
+            "edges": [{"from": "A", "to": "B", "conn": {
+                "line_style": "Normal", "arrow_start": null, "arrow_end": null,
+                "label": "", "rank": 1, "inline_style": null
+            }}]
+        }"#;
+        let err = serde_json::from_str::<Flowchart>(json).unwrap_err();
+        assert!(err.to_string().contains("unknown node \"A\""), "{}", err);
+    }
+}
@@ -0,0 +1,71 @@
+use super::Flowchart;
+use petgraph::algo;
+use std::fmt;
+
+impl<'input> Flowchart<'input> {
+    /// Look for things that are allowed, but are probably mistakes.
+    ///
+    /// The flowchart can still be used whatever this returns. Cycles are reported too, which only
+    /// matters if the flowchart is meant to be acyclic (ignore [`Warning::Cycle`] otherwise).
+    /// Nodes defined twice with different labels aren't reported, because they are a parse error.
+    pub fn validate(&self) -> Vec<Warning<'input>> {
+        let mut ids = self.nodes.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        let mut warnings = vec![];
+        for &id in &ids {
+            if self.nodes[id].is_id() {
+                warnings.push(Warning::ImplicitNode(id));
+            }
+        }
+        for &id in &ids {
+            if !self.graph.contains_node(id) {
+                warnings.push(Warning::OrphanNode(id));
+            }
+        }
+        let mut cycles = algo::tarjan_scc(&self.graph)
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.graph.contains_edge(component[0], component[0])
+            })
+            .map(|mut component| {
+                component.sort_unstable();
+                component
+            })
+            .collect::<Vec<_>>();
+        cycles.sort_unstable();
+        warnings.extend(cycles.into_iter().map(Warning::Cycle));
+        warnings
+    }
+}
+
+/// Something [`Flowchart::validate`] found that is probably a mistake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning<'input> {
+    /// A node that is only ever referred to by its id, so it never gets a label or shape of its
+    /// own (the inner value is its id).
+    ImplicitNode(&'input str),
+    /// A node without any edges (the inner value is its id).
+    OrphanNode(&'input str),
+    /// The ids of some nodes that are connected in a cycle (including a node linked to itself).
+    Cycle(Vec<&'input str>),
+}
+
+impl fmt::Display for Warning<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::ImplicitNode(id) => write!(f, "node `{}` is never given a label", id),
+            Warning::OrphanNode(id) => write!(f, "node `{}` isn't connected to anything", id),
+            Warning::Cycle(ids) => {
+                write!(f, "there is a cycle through nodes ")?;
+                for (idx, id) in ids.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{}`", id)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
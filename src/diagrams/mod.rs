@@ -1,5 +1,155 @@
 pub mod flowchart;
 pub mod pie;
+#[cfg(feature = "svg")]
+mod svg;
 
 pub use flowchart::Flowchart;
 pub use pie::Pie;
+
+use anyhow::{anyhow, Context, Result};
+#[cfg(any(feature = "svg", feature = "bitmap"))]
+use std::io;
+
+/// Any of the supported diagram types.
+#[derive(Debug)]
+pub enum Diagram<'input> {
+    /// A flowchart (`flowchart` or `graph`).
+    Flowchart(Flowchart<'input>),
+    /// A pie chart (`pie`).
+    Pie(Pie<'input>),
+}
+
+impl<'input> Diagram<'input> {
+    /// Parse a diagram, working out what type it is from the first keyword.
+    pub fn parse(src: &'input str) -> Result<Self> {
+        let keyword = src
+            .lines()
+            .map(str::trim)
+            // skip blank lines and comments
+            .find(|line| !line.is_empty() && !line.starts_with("%%"))
+            .and_then(|line| line.split_whitespace().next())
+            .unwrap_or("");
+        // like mermaid, the keyword isn't case sensitive
+        match keyword.to_ascii_lowercase().as_str() {
+            "flowchart" | "graph" => Ok(Diagram::Flowchart(Flowchart::parse(src)?)),
+            "pie" => Ok(Diagram::Pie(Pie::parse(src)?)),
+            _ => Err(anyhow!(
+                "unknown diagram type {:?} (supported types are `flowchart`, `graph` and `pie`)",
+                keyword
+            )),
+        }
+    }
+
+    /// Parse all the diagrams in `src`, for example the concatenated code blocks of a markdown
+    /// file.
+    ///
+    /// A new diagram starts at each line beginning (in the first column) with a header like
+    /// `flowchart LR` or `pie title Pets`, and runs until the next one. The word after
+    /// `flowchart` or `graph` has to be a direction, and the word after `pie` has to be
+    /// `showData` or `title`, if there is one. Lines starting with a markdown code fence (```)
+    /// also end a diagram, and any text that isn't part of a diagram is ignored. Each diagram is
+    /// parsed separately, so if one fails the rest are still parsed. Errors say which line the
+    /// failing diagram started on, since the positions they contain are from the start of that
+    /// diagram.
+    pub fn parse_all(src: &'input str) -> Vec<Result<Self>> {
+        let mut diagrams = vec![];
+        // byte offset and line number of the start of the current diagram, if in one
+        let mut current: Option<(usize, usize)> = None;
+        let mut offset = 0;
+        for (line_idx, line) in src.split_inclusive('\n').enumerate() {
+            let is_header = is_header(line);
+            if is_header || line.starts_with("```") {
+                if let Some((start, start_line)) = current.take() {
+                    diagrams.push(parse_part(&src[start..offset], start_line));
+                }
+            }
+            if is_header {
+                current = Some((offset, line_idx + 1));
+            }
+            offset += line.len();
+        }
+        if let Some((start, start_line)) = current {
+            diagrams.push(parse_part(&src[start..], start_line));
+        }
+        diagrams
+    }
+}
+
+/// Whether `line` starts a diagram (see [`Diagram::parse_all`]).
+///
+/// As well as starting with a diagram keyword, the rest of the line has to look like a header,
+/// so that a line like `pie --> chart` in the middle of a flowchart doesn't split it in two.
+fn is_header(line: &str) -> bool {
+    if line.starts_with(char::is_whitespace) {
+        return false;
+    }
+    let mut words = line.split_whitespace();
+    let keyword = words.next().unwrap_or("").to_ascii_lowercase();
+    match (keyword.as_str(), words.next()) {
+        ("flowchart" | "graph", None) => true,
+        ("flowchart" | "graph", Some(word)) => word.parse::<flowchart::Direction>().is_ok(),
+        ("pie", next) => matches!(next, None | Some("showData") | Some("title")),
+        _ => false,
+    }
+}
+
+/// Parse one of the diagrams found by [`Diagram::parse_all`].
+fn parse_part(src: &str, start_line: usize) -> Result<Diagram<'_>> {
+    Diagram::parse(src).with_context(|| format!("in diagram starting on line {}", start_line))
+}
+
+/// Convert an error from drawing a chart, for the functions that write out images.
+#[cfg(any(feature = "svg", feature = "bitmap"))]
+pub(crate) fn render_error(e: piet::Error) -> io::Error {
+    // piet errors aren't `Send`, so only the message can be kept
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        assert!(is_header("flowchart LR\n"));
+        assert!(is_header("graph TD A-->B"));
+        assert!(is_header("FLOWCHART\n"));
+        assert!(is_header("pie\n"));
+        assert!(is_header("pie showData title Pets"));
+        assert!(is_header("pie title Pets"));
+
+        // the keyword has to be followed by something that could be a header
+        assert!(!is_header("pie --> chart"));
+        assert!(!is_header("graph-->pie"));
+        assert!(!is_header("flowchart A --> B"));
+        assert!(!is_header("pie: 10"));
+        // and be in the first column
+        assert!(!is_header("  pie\n"));
+    }
+
+    #[test]
+    fn parse_all_keeps_going() {
+        let src = "\
+flowchart LR
+  A --> B
+pie --> chart
+pie title Broken
+  \"a\" : nope
+graph TD
+  C --> D
+pie
+  \"b\" : 1
+";
+        let diagrams = Diagram::parse_all(src);
+        assert_eq!(diagrams.len(), 4);
+        // `pie --> chart` is part of the first flowchart, not a new diagram
+        match &diagrams[0] {
+            Ok(Diagram::Flowchart(flow)) => assert!(flow.graph.contains_edge("pie", "chart")),
+            other => panic!("expected a flowchart, got {:?}", other),
+        }
+        let err = diagrams[1].as_ref().unwrap_err();
+        assert!(format!("{:#}", err).contains("line 4"), "{:#}", err);
+        assert!(matches!(diagrams[2], Ok(Diagram::Flowchart(_))));
+        assert!(matches!(diagrams[3], Ok(Diagram::Pie(_))));
+    }
+}
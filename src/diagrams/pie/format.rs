@@ -0,0 +1,73 @@
+use super::{Error, Pie};
+use crate::text::escape_quotes;
+use std::fmt::Write;
+
+impl<'input> Pie<'input> {
+    /// Write this chart back out as mermaid source.
+    ///
+    /// The output is in a canonical form: the header on one line, followed by one data point per
+    /// line, indented by 4 spaces. Parsing the output gives back an equivalent chart.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("pie");
+        if self.show_data {
+            out.push_str(" showData");
+        }
+        if !self.title.is_empty() {
+            write!(out, " title {}", self.title).unwrap();
+        }
+        out.push('\n');
+        for datum in &self.data {
+            let label = escape_quotes(datum.label);
+            writeln!(out, "    \"{}\" : {}", label, datum.value).unwrap();
+        }
+        out
+    }
+
+    /// Parse some pie chart source, and write it back out in the canonical form (see
+    /// [`Pie::to_mermaid`]).
+    ///
+    /// Formatting source that is already in the canonical form doesn't change it.
+    pub fn format(input: &str) -> Result<String, Error> {
+        Ok(Pie::parse(input)?.to_mermaid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagrams::pie::Datum, text::unescape};
+
+    #[test]
+    fn messy_input() {
+        let src = "  pie   showData  title  Pets\n\n\"Dogs\":386\n   \"Cats\" 85.5 %% fewer\n";
+        let formatted = Pie::format(src).unwrap();
+        assert_eq!(
+            formatted,
+            "pie showData title Pets\n    \"Dogs\" : 386\n    \"Cats\" : 85.5\n"
+        );
+        assert_eq!(Pie::format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn quotes_in_labels() {
+        let pie = Pie {
+            title: "",
+            show_data: false,
+            data: vec![
+                Datum {
+                    label: "say \"hi\"",
+                    value: 1.,
+                },
+                Datum {
+                    label: "already \\\"escaped\\\"",
+                    value: 2.,
+                },
+            ],
+        };
+        let src = pie.to_mermaid();
+        let parsed = Pie::parse(&src).unwrap();
+        assert_eq!(unescape(parsed.data[0].label), "say \"hi\"");
+        assert_eq!(unescape(parsed.data[1].label), "already \"escaped\"");
+        assert_eq!(Pie::format(&src).unwrap(), src);
+    }
+}
@@ -1,42 +1,125 @@
 //! Types and functions for creating pie charts.
 
-use crate::style::{ColorPalette, DefaultPalette, StrokeStyle, TextStyle};
+#[cfg(any(feature = "svg", feature = "bitmap"))]
+use super::render_error;
+#[cfg(feature = "svg")]
+use super::svg;
+use crate::complexity::Complexity;
+#[cfg(feature = "render")]
+use crate::style::{
+    Color, ColorPalette, DefaultPalette, LineJoin, NumberFormat, PatternPalette, Side, StrokeStyle,
+    TextMeasurer, TextStyle,
+};
 use anyhow::Result;
-use kurbo::Size;
+#[cfg(feature = "render")]
+use kurbo::{Point, Size};
 use nom::Finish;
+#[cfg(feature = "render")]
 use once_cell::sync::Lazy;
-use piet::{Color, RenderContext};
-use std::{fmt, fs, io, path::Path};
+#[cfg(feature = "render")]
+use piet::RenderContext;
+#[cfg(feature = "bitmap")]
+use piet::{ImageBuf, ImageFormat};
+#[cfg(feature = "render")]
+use std::{collections::HashMap, fmt, sync::Arc};
+#[cfg(any(feature = "svg", feature = "bitmap"))]
+use std::{fs, io, path::Path};
 
+mod format;
+mod owned;
 mod parse;
+#[cfg(feature = "render")]
 mod render;
+mod validate;
 
+pub use owned::{DatumOwned, PieOwned};
 pub use parse::{Error, ErrorKind};
+pub use validate::Warning;
+
+/// The canvas size used when exporting images, unless a custom size is given.
+///
+/// This will be superseded by per-style configuration.
+#[cfg(feature = "render")]
+pub const DEFAULT_CANVAS: Size = Size::new(800., 800.);
+/// The default radius of the pie (see [`PieStyle::radius`]).
+#[cfg(feature = "render")]
+pub const DEFAULT_RADIUS: f64 = 100.;
+/// The default narrowest segment that gets a label, 5 degrees (see
+/// [`PieStyle::min_label_sweep`]).
+#[cfg(feature = "render")]
+pub const DEFAULT_MIN_LABEL_SWEEP: f64 = 5. * std::f64::consts::PI / 180.;
 
 /// The default style used with [`Pie::render`].
+#[cfg(feature = "render")]
 pub static DEFAULT_STYLE: Lazy<PieStyle> = Lazy::new(PieStyle::default);
 /// A default style for use with dark themes.
+#[cfg(feature = "render")]
 pub static DARK_STYLE: Lazy<PieStyle> = Lazy::new(PieStyle::default_dark);
 
 /// A parsed pie chart.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pie<'input> {
     /// A title to display above the chart.
+    ///
+    /// If this is empty, no title is drawn and no space is left for one.
     pub title: &'input str,
     /// Whether to show the values of the data in the legend.
     pub show_data: bool,
     /// The data to chart.
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub data: Vec<Datum<'input>>,
 }
 
 impl<'input> Pie<'input> {
     /// Parse a chart description.
+    ///
+    /// Values can use commas to separate groups of thousands (`1,234.5`), but the decimal point
+    /// must be a `.`: European style values like `1.234,5` are an error.
+    ///
+    /// Comments (from `%%` to the end of the line) and blank lines can go anywhere whitespace
+    /// can. They are skipped over, so the positions in any [`Error`] still match the input.
     pub fn parse(src: &'input str) -> Result<Self, Error> {
-        let (_, pie) = parse::parse_pie(src).finish()?;
+        Self::parse_with_options(src, &PieParseOptions::default())
+    }
+
+    /// Like [`Pie::parse`], but with options to control how the description is interpreted.
+    pub fn parse_with_options(src: &'input str, options: &PieParseOptions) -> Result<Self, Error> {
+        let (_, pie) = parse::parse_pie(src, options).finish()?;
         Ok(pie)
     }
 
+    /// Get a rough estimate of how expensive this chart will be to render.
+    pub fn complexity(&self) -> Complexity {
+        Complexity::layered(self.data.len(), 0)
+    }
+
+    /// Set whether to show the values of the data in the legend, overriding `showData` in the
+    /// source.
+    pub fn with_show_data(mut self, show_data: bool) -> Self {
+        self.show_data = show_data;
+        self
+    }
+
+    /// Set the title, overriding any title in the source.
+    pub fn with_title(mut self, title: &'input str) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Remove the title, so the chart is drawn without one (and without space for one).
+    pub fn without_title(mut self) -> Self {
+        self.title = "";
+        self
+    }
+}
+
+#[cfg(feature = "render")]
+impl<'input> Pie<'input> {
     /// Use a [`piet::RenderContext`] to render this chart.
+    ///
+    /// Fails with a [`piet::Error::BackendError`] holding [`ErrorKind::NoData`] or
+    /// [`ErrorKind::ZeroTotal`] if there are no data points, or they are all zero.
     pub fn render<RC: RenderContext>(&self, ctx: &mut RC) -> Result<(), piet::Error> {
         self.render_with_style(&DEFAULT_STYLE, ctx)
     }
@@ -47,21 +130,134 @@ impl<'input> Pie<'input> {
         style: &PieStyle,
         ctx: &mut RC,
     ) -> Result<(), piet::Error> {
-        render::render(self, style, ctx)
+        let mut text = ctx.text().clone();
+        render::render(self, style, ctx, &mut text)
+    }
+
+    /// Like [`Pie::render_with_style`] but lays out text using `text` rather than `ctx.text()`.
+    ///
+    /// This is useful if you have loaded custom fonts into a text factory. `text` must come from
+    /// the same piet backend as `ctx` (and so the same version of `piet`); this is enforced by the
+    /// type system.
+    pub fn render_with_text<RC: RenderContext>(
+        &self,
+        style: &PieStyle,
+        ctx: &mut RC,
+        text: &mut RC::Text,
+    ) -> Result<(), piet::Error> {
+        render::render(self, style, ctx, text)
+    }
+
+    /// Render only the selected parts of this chart.
+    ///
+    /// The background is not cleared, and the parts that are left out don't take up any space, so
+    /// this can be used to compose charts into custom layouts (for example several pies sharing
+    /// a single legend). Use [`Pie::parts_size`] to find out how much space the parts need.
+    /// Charts with nothing to draw are an error (see [`Pie::render`]) if the pie is included.
+    pub fn render_parts<RC: RenderContext>(
+        &self,
+        parts: RenderParts,
+        style: &PieStyle,
+        ctx: &mut RC,
+    ) -> Result<(), piet::Error> {
+        let mut text = ctx.text().clone();
+        render::render_parts(self, parts, style, ctx, &mut text)
+    }
+
+    /// Get where each segment of the chart will be drawn, for example to work out which segment
+    /// is under the mouse.
+    ///
+    /// The segments are in the order they are drawn (see [`PieStyle::sort`]), and positions are in
+    /// the same coordinates used by [`Pie::render_with_style`]. Text is measured using `ctx`.
+    pub fn segment_geometry<RC: RenderContext>(
+        &self,
+        style: &PieStyle,
+        ctx: &mut RC,
+    ) -> Result<Vec<SegmentGeometry<'input>>, piet::Error> {
+        let mut text = ctx.text().clone();
+        render::segment_geometry(self, style, &mut text)
+    }
+
+    /// Measure the size of the canvas needed to fit this chart when rendered with `style`.
+    ///
+    /// Text is measured using `ctx`, so the result is only exact for the same backend.
+    pub fn size<RC: RenderContext>(
+        &self,
+        style: &PieStyle,
+        ctx: &mut RC,
+    ) -> Result<Size, piet::Error> {
+        self.parts_size(RenderParts::ALL, style, ctx)
+    }
+
+    /// Like [`Pie::size`], but only measures the selected parts (see [`Pie::render_parts`]).
+    pub fn parts_size<RC: RenderContext>(
+        &self,
+        parts: RenderParts,
+        style: &PieStyle,
+        ctx: &mut RC,
+    ) -> Result<Size, piet::Error> {
+        let mut text = ctx.text().clone();
+        self.measure(parts, style, &mut text)
+    }
+
+    /// Like [`Pie::parts_size`], but measures text using a [`TextMeasurer`].
+    pub fn measure(
+        &self,
+        parts: RenderParts,
+        style: &PieStyle,
+        text: &mut dyn TextMeasurer,
+    ) -> Result<Size, piet::Error> {
+        render::measure(self, parts, style, text)
     }
 
     /// Write out an svg image to `writer`, with optional custom styling.
+    ///
+    /// Each segment and legend entry gets a `<title>` describing its data point (see
+    /// `svg::insert_tooltips`). The background is transparent unless
+    /// [`PieStyle::background_color`] is set.
+    #[cfg(feature = "svg")]
     pub fn to_svg(&self, writer: impl io::Write, style: Option<&PieStyle>) -> io::Result<()> {
-        let mut rc = piet_svg::RenderContext::new(Size::new(800., 800.));
-        if let Some(style) = style {
-            self.render_with_style(style, &mut rc).unwrap();
-        } else {
-            self.render(&mut rc).unwrap();
-        }
-        rc.write(writer)
+        self.to_svg_sized(writer, Some(DEFAULT_CANVAS), style)
+    }
+
+    /// Like [`Pie::to_svg`], but with a custom canvas size.
+    ///
+    /// If `size` is `None`, the canvas will be sized to fit the chart exactly.
+    #[cfg(feature = "svg")]
+    pub fn to_svg_sized(
+        &self,
+        writer: impl io::Write,
+        size: Option<Size>,
+        style: Option<&PieStyle>,
+    ) -> io::Result<()> {
+        let style = style.unwrap_or(&DEFAULT_STYLE);
+        let size = match size {
+            Some(size) => size,
+            None => {
+                let mut rc = piet_svg::RenderContext::new(Size::ZERO);
+                self.size(style, &mut rc).map_err(render_error)?
+            }
+        };
+        svg::write_svg(writer, size, |rc| {
+            self.render_with_style(style, rc)?;
+            render::tooltips(self, style, rc)
+        })
+    }
+
+    /// Render an svg image to a string, with optional custom styling.
+    ///
+    /// This doesn't touch the filesystem or need a `piet_common::Device`, so it works on targets
+    /// like WebAssembly. Build with `default-features = false` and the `svg` feature to avoid
+    /// depending on the bitmap backends at all.
+    #[cfg(feature = "svg")]
+    pub fn to_svg_string(&self, style: Option<&PieStyle>) -> io::Result<String> {
+        let mut buf = vec![];
+        self.to_svg(&mut buf, style)?;
+        String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
     /// Write out an svg image to a file at `filename`, with optional custom styling.
+    #[cfg(feature = "svg")]
     pub fn to_svg_file(
         &self,
         filename: impl AsRef<Path>,
@@ -75,83 +271,407 @@ impl<'input> Pie<'input> {
     /// Write out a png image to a file at `filename`, with optional custom styling.
     ///
     /// `px_scale` allows for rendering at a larger scale, either for extra zoom or for high DPI
-    /// screens.
+    /// screens. The background is transparent unless [`PieStyle::background_color`] is set.
+    #[cfg(feature = "bitmap")]
     pub fn to_png_file(
         &self,
         filename: impl AsRef<Path>,
         px_scale: f64,
         style: Option<&PieStyle>,
     ) -> io::Result<()> {
-        let mut device = piet_common::Device::new().unwrap();
-        let size = (800. * px_scale) as usize;
-        let mut bitmap = device.bitmap_target(size, size, px_scale).unwrap();
-        let mut rc = bitmap.render_context();
-        if let Some(style) = style {
-            self.render_with_style(style, &mut rc).unwrap();
-        } else {
-            self.render(&mut rc).unwrap();
-        }
-        rc.finish().unwrap();
-        drop(rc);
+        self.to_png_file_sized(filename, Some(DEFAULT_CANVAS), px_scale, style)
+    }
 
-        bitmap.save_to_file(filename).unwrap();
+    /// Like [`Pie::to_png_file`], but with a custom canvas size.
+    ///
+    /// `size` is before scaling by `px_scale`. If it is `None`, the canvas will be sized to fit
+    /// the chart exactly.
+    #[cfg(feature = "bitmap")]
+    pub fn to_png_file_sized(
+        &self,
+        filename: impl AsRef<Path>,
+        size: Option<Size>,
+        px_scale: f64,
+        style: Option<&PieStyle>,
+    ) -> io::Result<()> {
+        let file = io::BufWriter::new(fs::File::create(filename)?);
+        self.to_png_sized(file, size, px_scale, style)
+    }
+
+    /// Render a png image into memory, with optional custom styling.
+    ///
+    /// See [`Pie::to_png_file`] for the meaning of `px_scale`.
+    #[cfg(feature = "bitmap")]
+    pub fn to_png_bytes(&self, px_scale: f64, style: Option<&PieStyle>) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.to_png_sized(&mut buf, Some(DEFAULT_CANVAS), px_scale, style)?;
+        Ok(buf)
+    }
+
+    /// Write out a png image to `writer`, with a custom canvas size and optional custom styling.
+    ///
+    /// `size` is before scaling by `px_scale`. If it is `None`, the canvas will be sized to fit
+    /// the chart exactly.
+    #[cfg(feature = "bitmap")]
+    pub fn to_png_sized(
+        &self,
+        writer: impl io::Write,
+        size: Option<Size>,
+        px_scale: f64,
+        style: Option<&PieStyle>,
+    ) -> io::Result<()> {
+        let image = self
+            .to_image_sized(size, px_scale, style)
+            .map_err(render_error)?;
+        let mut encoder = png::Encoder::new(writer, image.width() as u32, image.height() as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()?
+            .write_image_data(image.raw_pixels())?;
         Ok(())
     }
+
+    /// Render the chart into an image in memory, with optional custom styling.
+    ///
+    /// The pixels are RGBA, with the alpha not premultiplied (so they can be passed straight to
+    /// e.g. `image::RgbaImage::from_raw`). See [`Pie::to_png_file`] for the meaning of
+    /// `px_scale`.
+    #[cfg(feature = "bitmap")]
+    pub fn to_image(
+        &self,
+        px_scale: f64,
+        style: Option<&PieStyle>,
+    ) -> Result<ImageBuf, piet::Error> {
+        self.to_image_sized(Some(DEFAULT_CANVAS), px_scale, style)
+    }
+
+    /// Like [`Pie::to_image`], but with a custom canvas size.
+    ///
+    /// `size` is before scaling by `px_scale`. If it is `None`, the canvas will be sized to fit
+    /// the chart exactly.
+    #[cfg(feature = "bitmap")]
+    pub fn to_image_sized(
+        &self,
+        size: Option<Size>,
+        px_scale: f64,
+        style: Option<&PieStyle>,
+    ) -> Result<ImageBuf, piet::Error> {
+        let style = style.unwrap_or(&DEFAULT_STYLE);
+        let mut device = piet_common::Device::new()?;
+        let size = match size {
+            Some(size) => size,
+            None => {
+                let mut bitmap = device.bitmap_target(1, 1, px_scale)?;
+                let mut rc = bitmap.render_context();
+                let size = self.size(style, &mut rc)?;
+                rc.finish()?;
+                size
+            }
+        };
+        let width = (size.width * px_scale).ceil() as usize;
+        let height = (size.height * px_scale).ceil() as usize;
+        let mut bitmap = device.bitmap_target(width, height, px_scale)?;
+        let mut rc = bitmap.render_context();
+        self.render_with_style(style, &mut rc)?;
+        rc.finish()?;
+        drop(rc);
+        bitmap.to_image_buf(ImageFormat::RgbaSeparate)
+    }
 }
 
 /// A numeric data point in the pie chart.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Datum<'input> {
     /// What to label this data point in the legend.
+    ///
+    /// The label is as written in the source: use [`unescape`](crate::text::unescape) to get the
+    /// text to display.
     pub label: &'input str,
     /// The data value.
     pub value: f64,
 }
 
+/// Where a segment of a pie chart is drawn.
+///
+/// Angles are in radians, measured clockwise from the positive x axis (so the first segment
+/// starts at `-PI / 2`, straight up).
+#[cfg(feature = "render")]
+#[derive(Debug, Copy, Clone)]
+pub struct SegmentGeometry<'input> {
+    /// The label of the data point this segment shows.
+    pub label: &'input str,
+    /// The value of the data point this segment shows.
+    pub value: f64,
+    /// The angle the segment starts at.
+    pub start_angle: f64,
+    /// How far round the segment goes.
+    pub sweep_angle: f64,
+    /// The center of the pie.
+    pub center: Point,
+    /// The outer radius of the pie.
+    pub radius: f64,
+    /// The inner radius of the pie, which is 0 unless the pie is a donut.
+    pub inner_radius: f64,
+}
+
+#[cfg(feature = "render")]
+impl SegmentGeometry<'_> {
+    /// Whether `point` is inside this segment.
+    pub fn contains(&self, point: Point) -> bool {
+        let offset = point - self.center;
+        let distance = offset.hypot();
+        if distance > self.radius || distance < self.inner_radius {
+            return false;
+        }
+        let angle = (offset.atan2() - self.start_angle).rem_euclid(std::f64::consts::TAU);
+        angle < self.sweep_angle
+    }
+}
+
 /// Styling for the pie chart.
+#[cfg(feature = "render")]
 #[derive(Clone)]
 pub struct PieStyle {
-    /// What color to clear the background with.
-    ///
-    /// The default is transparent.
+    /// The background color (see [`clear_background`](crate::style::clear_background)).
     pub background_color: Color,
     /// How to style the title text.
     pub title: TextStyle,
     /// How to style the outline of pie segments.
+    ///
+    /// The default uses round joins, to avoid spikes at the center of the pie when there are
+    /// thin segments.
     pub segment_outline: StrokeStyle,
+    /// Custom outline styles for particular segments, overriding `segment_outline`.
+    ///
+    /// Keys are the index of the segment in the order they are drawn (the same index used for
+    /// `segment_colors`). Indices that don't match a segment are ignored.
+    pub segment_stroke_overrides: HashMap<usize, StrokeStyle>,
     /// How to choose the color of each pie segment.
     pub segment_colors: Box<dyn ColorPalette + Send + Sync>,
-    /// How to style segment labels (showing the percentage of the total a particular segment takes
-    /// up).
+    /// Whether to fill pie segments with their color.
+    ///
+    /// If this is `false`, segments are only outlined, leaving the background showing through.
+    /// The legend still uses `segment_colors`.
+    pub segment_fill: bool,
+    /// How to choose a hatching pattern to draw over each pie segment, or `None` to only use
+    /// color.
+    ///
+    /// Patterns are drawn in the color of `segment_outline`, over the segments and the legend
+    /// swatches. The default is `None`.
+    pub segment_patterns: Option<Box<dyn PatternPalette + Send + Sync>>,
+    /// The radius of the hole in the middle of the chart, as a fraction of the pie radius.
+    ///
+    /// `0.0` draws a full pie, and something like `0.5` draws a donut. The default is `0.0`.
+    pub inner_radius_ratio: f64,
+    /// The radius of the pie.
+    pub radius: f64,
+    /// The space left around the edge of the chart.
+    pub margin: f64,
+    /// The space between the title and the pie.
+    pub title_gap: f64,
+    /// The space between the pie and the legend.
+    pub legend_gap: f64,
+    /// The space around the entries in the legend.
+    pub legend_padding: f64,
+    /// The width of the outline around the legend and its color squares.
+    pub legend_outline_width: f64,
+    /// The order to draw the segments (and legend entries) in.
+    ///
+    /// The default is to keep the order from the source.
+    pub sort: PieSort,
+    /// If set, data points smaller than this fraction of the total are combined into a single
+    /// "Other" segment, drawn last.
+    ///
+    /// Nothing is combined if only one data point is below the threshold. The default is `None`.
+    pub collapse_below: Option<f64>,
+    /// How to style segment labels (by default showing the percentage of the total a particular
+    /// segment takes up).
     ///
     /// If this is `None` then labels will not be drawn.
     pub segment_label: Option<TextStyle>,
+    /// What to write in segment labels.
+    ///
+    /// The default is [`SegmentLabelContent::Percent`].
+    pub segment_label_content: SegmentLabelContent,
+    /// Segments narrower than this angle (in radians) don't get a label, because it wouldn't fit.
+    ///
+    /// The default is [`DEFAULT_MIN_LABEL_SWEEP`].
+    pub min_label_sweep: f64,
     /// How to style the labels for each data point in the legend.
     pub legend_label: TextStyle,
+    /// How to write out data values, when they are shown in the legend (see [`Pie::show_data`])
+    /// and in svg tooltips.
+    ///
+    /// If this is `None`, values are written as plain numbers. See
+    /// [`PieStyle::with_number_format`] for common formatting options.
+    pub value_format: Option<Arc<dyn Fn(f64) -> String + Send + Sync>>,
+    /// Whether the color swatch for each legend entry goes before or after its label.
+    ///
+    /// The default is [`Side::Before`].
+    pub legend_marker_side: Side,
+    /// Whether to line outlines up with the pixel grid, so they look crisp in bitmaps.
+    ///
+    /// Outline widths are rounded to a whole number of device pixels (at least one), so a width
+    /// of `1.0` is exactly one pixel at a `px_scale` of 1 and two at a `px_scale` of 2. The
+    /// legend boxes are also moved slightly so their outlines cover whole pixels. The size of a
+    /// device pixel is taken from the render context's transform. The default is `false`.
+    pub snap_to_pixels: bool,
+}
+
+/// The order in which to draw the data in a pie chart.
+///
+/// Segment colors are chosen after sorting, so the first segment drawn always gets the first
+/// color from the palette.
+#[cfg(feature = "render")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PieSort {
+    /// Keep the order the data appeared in the source.
+    None,
+    /// Largest values first (this is what `mermaid.js` does).
+    Descending,
+    /// Smallest values first.
+    Ascending,
+}
+
+/// What to write in the label inside each pie segment.
+#[cfg(feature = "render")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SegmentLabelContent {
+    /// The percentage of the total the segment takes up (e.g. `42%`).
+    Percent,
+    /// The value of the data point, written using [`PieStyle::value_format`].
+    Value,
+    /// The label of the data point, with the percentage on the line below.
+    LabelAndPercent,
+    /// Nothing, the same as setting [`PieStyle::segment_label`] to `None`.
+    None,
+}
+
+/// Options for [`Pie::parse_with_options`].
+#[derive(Debug, Clone)]
+pub struct PieParseOptions {
+    /// Whether to remove whitespace from the start and end of quoted labels, like the title.
+    ///
+    /// When `false`, spaces inside the quotes are kept. Defaults to `true`.
+    pub trim_labels: bool,
+    /// Whether the values are meant to be percentages, rather than raw counts.
+    ///
+    /// The chart is drawn the same way either way, but [`Pie::validate`] will warn if the values
+    /// don't add up to (roughly) 100. Defaults to `false`.
+    pub assume_percentages: bool,
+    /// Whether it's an error for the chart to have nothing to draw, because there are no data
+    /// points ([`ErrorKind::NoData`]) or they are all zero ([`ErrorKind::ZeroTotal`]).
+    ///
+    /// When `false`, charts with just a header (e.g. `pie title Coming soon`) parse, but rendering
+    /// them fails with the same errors. Defaults to `false`.
+    pub require_data: bool,
+}
+
+impl Default for PieParseOptions {
+    fn default() -> Self {
+        Self {
+            trim_labels: true,
+            assume_percentages: false,
+            require_data: false,
+        }
+    }
+}
+
+/// Which parts of a chart to draw with [`Pie::render_parts`].
+#[cfg(feature = "render")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderParts {
+    /// Draw the pie itself.
+    pub pie: bool,
+    /// Draw the legend.
+    pub legend: bool,
+    /// Draw the title.
+    pub title: bool,
+}
+
+#[cfg(feature = "render")]
+impl RenderParts {
+    /// Everything, as drawn by [`Pie::render`].
+    pub const ALL: RenderParts = RenderParts {
+        pie: true,
+        legend: true,
+        title: true,
+    };
 }
 
+#[cfg(feature = "render")]
+impl Default for RenderParts {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+#[cfg(feature = "render")]
 impl fmt::Debug for PieStyle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("PieStyle")
             .field("background_color", &self.background_color)
             .field("title", &self.title)
             .field("segment_outline", &self.segment_outline)
+            .field("segment_stroke_overrides", &self.segment_stroke_overrides)
             .field("segment_colors", &"dyn ColorPalette")
+            .field("segment_fill", &self.segment_fill)
+            .field(
+                "segment_patterns",
+                &self.segment_patterns.as_ref().map(|_| "dyn PatternPalette"),
+            )
+            .field("inner_radius_ratio", &self.inner_radius_ratio)
+            .field("radius", &self.radius)
+            .field("margin", &self.margin)
+            .field("title_gap", &self.title_gap)
+            .field("legend_gap", &self.legend_gap)
+            .field("legend_padding", &self.legend_padding)
+            .field("legend_outline_width", &self.legend_outline_width)
+            .field("sort", &self.sort)
+            .field("collapse_below", &self.collapse_below)
             .field("segment_label", &self.segment_label)
+            .field("segment_label_content", &self.segment_label_content)
+            .field("min_label_sweep", &self.min_label_sweep)
             .field("legend_label", &self.legend_label)
+            .field(
+                "value_format",
+                &self.value_format.as_ref().map(|_| "dyn Fn(f64) -> String"),
+            )
+            .field("legend_marker_side", &self.legend_marker_side)
+            .field("snap_to_pixels", &self.snap_to_pixels)
             .finish()
     }
 }
 
+#[cfg(feature = "render")]
 impl PieStyle {
     pub fn default() -> Self {
         Self {
             background_color: Color::TRANSPARENT,
             title: TextStyle::default().with_bold(true),
-            segment_outline: StrokeStyle::new(1.5, Color::BLACK),
+            segment_outline: StrokeStyle::new(1.5, Color::BLACK).with_line_join(LineJoin::Round),
+            segment_stroke_overrides: HashMap::new(),
             segment_colors: Box::new(DefaultPalette),
+            segment_fill: true,
+            segment_patterns: None,
+            inner_radius_ratio: 0.,
+            radius: DEFAULT_RADIUS,
+            margin: 10.,
+            title_gap: 10.,
+            legend_gap: 50.,
+            legend_padding: 5.,
+            legend_outline_width: 1.5,
+            sort: PieSort::None,
+            collapse_below: None,
             segment_label: Some(TextStyle::default_dark().with_font_size(12.)),
+            segment_label_content: SegmentLabelContent::Percent,
+            min_label_sweep: DEFAULT_MIN_LABEL_SWEEP,
             legend_label: TextStyle::default(),
+            value_format: None,
+            legend_marker_side: Side::Before,
+            snap_to_pixels: false,
         }
     }
     pub fn default_dark() -> Self {
@@ -160,4 +680,118 @@ impl PieStyle {
         this.legend_label = TextStyle::default_dark();
         this
     }
+
+    /// Set the color to clear the background with.
+    pub fn with_background_color(mut self, background_color: Color) -> Self {
+        self.background_color = background_color;
+        self
+    }
+
+    /// Set how to style the title text.
+    pub fn with_title_style(mut self, title: TextStyle) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Set how to style the outline of pie segments.
+    pub fn with_segment_outline(mut self, segment_outline: StrokeStyle) -> Self {
+        self.segment_outline = segment_outline;
+        self
+    }
+
+    /// Set how to choose the color of each pie segment.
+    pub fn with_segment_colors(
+        mut self,
+        segment_colors: impl ColorPalette + Send + Sync + 'static,
+    ) -> Self {
+        self.segment_colors = Box::new(segment_colors);
+        self
+    }
+
+    /// Set whether to fill pie segments, or only outline them.
+    pub fn with_segment_fill(mut self, segment_fill: bool) -> Self {
+        self.segment_fill = segment_fill;
+        self
+    }
+
+    /// Set how to choose a hatching pattern for each pie segment.
+    pub fn with_segment_patterns(
+        mut self,
+        segment_patterns: impl PatternPalette + Send + Sync + 'static,
+    ) -> Self {
+        self.segment_patterns = Some(Box::new(segment_patterns));
+        self
+    }
+
+    /// Set how to style segment labels, or `None` to not draw them.
+    pub fn with_segment_label(mut self, segment_label: Option<TextStyle>) -> Self {
+        self.segment_label = segment_label;
+        self
+    }
+
+    /// Set the narrowest angle (in radians) a segment can be and still get a label.
+    pub fn with_min_label_sweep(mut self, min_label_sweep: f64) -> Self {
+        self.min_label_sweep = min_label_sweep;
+        self
+    }
+
+    /// Set what to write in segment labels.
+    pub fn with_segment_label_content(mut self, content: SegmentLabelContent) -> Self {
+        self.segment_label_content = content;
+        self
+    }
+
+    /// Set whether to line outlines up with the pixel grid (see [`PieStyle::snap_to_pixels`]).
+    pub fn with_snap_to_pixels(mut self, snap_to_pixels: bool) -> Self {
+        self.snap_to_pixels = snap_to_pixels;
+        self
+    }
+
+    /// Set the radius of the pie.
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Set the space left around the edge of the chart.
+    pub fn with_margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Set how to style the labels in the legend.
+    pub fn with_legend_label(mut self, legend_label: TextStyle) -> Self {
+        self.legend_label = legend_label;
+        self
+    }
+
+    /// Set whether the color swatch for each legend entry goes before or after its label.
+    pub fn with_legend_marker_side(mut self, legend_marker_side: Side) -> Self {
+        self.legend_marker_side = legend_marker_side;
+        self
+    }
+
+    /// Set how to write out data values (e.g. `|v| format!("{} kg", v)`).
+    pub fn with_value_format(
+        mut self,
+        value_format: impl Fn(f64) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.value_format = Some(Arc::new(value_format));
+        self
+    }
+
+    /// Set how to write out data values using a [`NumberFormat`] (e.g. with a fixed number of
+    /// decimal places and a unit).
+    pub fn with_number_format(self, format: NumberFormat) -> Self {
+        self.with_value_format(move |value| format.format(value))
+    }
+
+    /// Write out a data value using `value_format`.
+    #[cfg(feature = "render")]
+    fn format_value(&self, value: f64) -> String {
+        match self.value_format {
+            Some(ref format) => format(value),
+            None => value.to_string(),
+        }
+    }
 }
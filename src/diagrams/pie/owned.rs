@@ -0,0 +1,99 @@
+use super::{Datum, Error, Pie};
+use std::str::FromStr;
+
+/// A version of [`Pie`] that owns its data, so it can outlive the input it was parsed from.
+///
+/// Use [`Pie::into_owned`] or [`str::parse`] to create one, and [`PieOwned::as_pie`] to get back a
+/// [`Pie`] borrowing from it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PieOwned {
+    /// A title to display above the chart.
+    pub title: String,
+    /// Whether to show the values of the data in the legend.
+    pub show_data: bool,
+    /// The data to chart.
+    pub data: Vec<DatumOwned>,
+}
+
+/// An owned version of [`Datum`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatumOwned {
+    /// What to label this data point in the legend.
+    pub label: String,
+    /// The data value.
+    pub value: f64,
+}
+
+impl Pie<'_> {
+    /// Copy all the data borrowed from the input, so the chart can outlive it.
+    pub fn into_owned(self) -> PieOwned {
+        PieOwned {
+            title: self.title.to_string(),
+            show_data: self.show_data,
+            data: self.data.into_iter().map(Datum::into_owned).collect(),
+        }
+    }
+}
+
+impl PieOwned {
+    /// Get a [`Pie`] that borrows its data from `self`.
+    pub fn as_pie(&self) -> Pie<'_> {
+        Pie {
+            title: &self.title,
+            show_data: self.show_data,
+            data: self.data.iter().map(DatumOwned::as_datum).collect(),
+        }
+    }
+}
+
+impl FromStr for PieOwned {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Pie::parse(s)?.into_owned())
+    }
+}
+
+impl Datum<'_> {
+    /// Copy the data borrowed from the input.
+    pub fn into_owned(self) -> DatumOwned {
+        DatumOwned {
+            label: self.label.to_string(),
+            value: self.value,
+        }
+    }
+}
+
+impl DatumOwned {
+    /// Get a [`Datum`] that borrows its data from `self`.
+    pub fn as_datum(&self) -> Datum<'_> {
+        Datum {
+            label: &self.label,
+            value: self.value,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "pie showData title Pets\n\"Dogs\": 386\n\"Cats\": 85.5\n\"Rats\": 15";
+
+    #[test]
+    fn json_round_trip() {
+        let pie = Pie::parse(SRC).unwrap();
+        let json = serde_json::to_string(&pie).unwrap();
+        let back: Pie = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.into_owned(), pie.into_owned());
+    }
+
+    #[test]
+    fn owned_json_round_trip() {
+        let pie = SRC.parse::<PieOwned>().unwrap();
+        let json = serde_json::to_string(&pie).unwrap();
+        assert_eq!(serde_json::from_str::<PieOwned>(&json).unwrap(), pie);
+    }
+}
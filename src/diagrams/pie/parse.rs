@@ -1,5 +1,14 @@
-use super::{Datum, Pie};
-use nom::{bytes::complete::take_until, character::complete::multispace0};
+use super::{Datum, Pie, PieParseOptions};
+use crate::text::closing_quote;
+use nom::{
+    branch::alt,
+    bytes::complete::take_till,
+    character::complete::{char, digit0, digit1, multispace1, one_of},
+    combinator::recognize,
+    multi::{many0_count, separated_list1},
+    sequence::{pair, tuple},
+    InputTake,
+};
 use nom_locate::LocatedSpan;
 use std::{fmt, num::ParseFloatError};
 
@@ -9,7 +18,7 @@ use std::{fmt, num::ParseFloatError};
 pub struct Error {
     /// (1-indexed) line number of the error.
     pub line: u32,
-    /// (1-indexed) column number of the error.
+    /// (1-indexed) column number of the error, counting characters rather than bytes.
     pub col: usize,
     /// (0-indexed) offset in the input string of the error.
     pub offset: usize,
@@ -20,7 +29,7 @@ impl Error {
     fn new(span: &Span<'_>, kind: ErrorKind) -> Self {
         Self {
             line: span.location_line(),
-            col: span.get_column(),
+            col: span.get_utf8_column(),
             offset: span.location_offset(),
             kind,
         }
@@ -38,6 +47,7 @@ impl fmt::Display for Error {
     }
 }
 impl std::error::Error for Error {}
+impl std::error::Error for ErrorKind {}
 
 /// Different types of parsing errors for the pie chart.
 #[derive(Debug)]
@@ -56,6 +66,18 @@ pub enum ErrorKind {
     SearchLiteral(&'static str),
     /// Expected to be at the end of the input, but found some more input.
     UnexpectedTrailing,
+    /// The chart doesn't contain any data points.
+    ///
+    /// Parsing only fails with this with [`PieParseOptions::require_data`], but drawing the pie
+    /// always does.
+    NoData,
+    /// All the data points in the chart are zero, so there is nothing to draw.
+    ///
+    /// Parsing only fails with this with [`PieParseOptions::require_data`], but drawing the pie
+    /// always does.
+    ZeroTotal,
+    /// A data point had a negative value (the inner value is its label).
+    NegativeValue(String),
 }
 
 impl fmt::Display for ErrorKind {
@@ -71,6 +93,11 @@ impl fmt::Display for ErrorKind {
             }
             ErrorKind::SearchLiteral(lit) => write!(f, "ran out of input searching for {:?}", lit),
             ErrorKind::UnexpectedTrailing => write!(f, "unexpected trailing characters"),
+            ErrorKind::NoData => write!(f, "expected at least one data point"),
+            ErrorKind::ZeroTotal => write!(f, "all data points are zero"),
+            ErrorKind::NegativeValue(label) => {
+                write!(f, "data point {:?} has a negative value", label)
+            }
         }
     }
 }
@@ -79,7 +106,10 @@ type Span<'input> = LocatedSpan<&'input str>;
 type IResult<'input, Out> = nom::IResult<Span<'input>, Out, Error>;
 
 /// input is expected to be pre-trimmed
-pub fn parse_pie(i: &str) -> IResult<Pie> {
+pub fn parse_pie<'input>(
+    i: &'input str,
+    options: &PieParseOptions,
+) -> IResult<'input, Pie<'input>> {
     let i = LocatedSpan::new(i);
     let (i, _) = ws(i)?;
     let (mut i, (title, show_data)) = parse_header(i)?;
@@ -91,13 +121,21 @@ pub fn parse_pie(i: &str) -> IResult<Pie> {
             break;
         }
         let datum;
-        (i, datum) = parse_datum(i)?;
+        (i, datum) = parse_datum(i, options)?;
         data.push(datum);
     }
     if !i.trim().is_empty() {
         // we will have tried to parse it above
         unreachable!()
     }
+    if options.require_data {
+        if data.is_empty() {
+            return Err(nom::Err::Error(Error::new(&i, ErrorKind::NoData)));
+        }
+        if data.iter().all(|d| d.value == 0.) {
+            return Err(nom::Err::Error(Error::new(&i, ErrorKind::ZeroTotal)));
+        }
+    }
     Ok((
         i,
         Pie {
@@ -109,7 +147,9 @@ pub fn parse_pie(i: &str) -> IResult<Pie> {
 }
 
 fn parse_header(i: Span<'_>) -> IResult<(Option<&str>, bool)> {
-    let (i, _) = tag("pie")(i)?;
+    // like mermaid, the diagram keyword isn't case sensitive
+    let (i, _) = nom::bytes::complete::tag_no_case("pie")(i)
+        .map_error(|_| ErrorKind::ExpectedLiteral("pie"))?;
     let (i, _) = ws(i)?;
     let (i, show_data) = opt(tag("showData"))(i)?;
     let (i, _) = ws(i)?;
@@ -118,35 +158,71 @@ fn parse_header(i: Span<'_>) -> IResult<(Option<&str>, bool)> {
 }
 
 /// Parses "title The title" into 'The title'.
+///
+/// The title runs to the end of the line.
 fn parse_title(i: Span) -> IResult<&str> {
     let (i, _) = tag("title")(i)?;
-    let (i, title) = take_until("\"")(i).map_error(|_| ErrorKind::SearchLiteral("\""))?;
+    let (i, title) = take_till(|c| c == '\n' || c == '\r')(i)
+        .map_err(|_: nom::Err<nom::error::Error<Span>>| unreachable!())?;
     Ok((i, title.fragment()))
 }
 
-/// Parse a data point.
+/// Parse a data point (`"label" : value`, or `"label" value`).
 ///
 /// Expect that whitespace has already been consumed.
-fn parse_datum(i: Span) -> IResult<Datum> {
+fn parse_datum<'input>(
+    i: Span<'input>,
+    options: &PieParseOptions,
+) -> IResult<'input, Datum<'input>> {
     let (i, label) = quoted(i)?;
+    let label = if options.trim_labels {
+        label.trim()
+    } else {
+        label
+    };
     let (i, _) = ws(i)?;
-    let (i, _) = tag(":")(i)?;
-    let (i, _) = ws(i)?;
-    let (i, value) = float(i)?;
+    // the colon is optional, so `"label" 42` works too
+    let (i, _) = opt(tag(":"))(i)?;
+    let (value_start, _) = ws(i)?;
+    let (i, value) = float(value_start)?;
+    if value < 0. {
+        let kind = ErrorKind::NegativeValue(label.to_string());
+        return Err(nom::Err::Error(Error::new(&value_start, kind)));
+    }
     Ok((i, Datum { label, value }))
 }
 
 /// A string surrouded by double quotes (")
+///
+/// Escaped quotes (`\"`) are kept in the string (see [`crate::text::unescape`]).
 fn quoted(i: Span) -> IResult<&str> {
     let (i, _) = tag("\"")(i)?;
-    let (i, label) = take_until("\"")(i).map_error(|_| ErrorKind::UnclosedQuote("\""))?;
+    let end = match closing_quote(i.fragment()) {
+        Some(end) => end,
+        None => {
+            let kind = ErrorKind::UnclosedQuote("\"");
+            return Err(nom::Err::Error(Error::new(&i, kind)));
+        }
+    };
+    let (i, label) = i.take_split(end);
     let (i, _) = tag("\"")(i)?;
     Ok((i, label.fragment()))
 }
 
-/// Whitespace using our error type
+/// Whitespace and `%%` comments, using our error type
+///
+/// Comments are skipped in place, so positions in the rest of the input are still correct.
 fn ws(i: Span) -> IResult<Span> {
-    multispace0(i).map_err(|_: nom::Err<nom::error::Error<Span>>| unreachable!())
+    recognize(many0_count(alt((multispace1, comment))))(i)
+        .map_err(|_: nom::Err<nom::error::Error<Span>>| unreachable!())
+}
+
+/// A comment, from `%%` to the end of the line.
+fn comment(i: Span) -> nom::IResult<Span, Span> {
+    recognize(pair(
+        nom::bytes::complete::tag("%%"),
+        take_till(|c| c == '\n' || c == '\r'),
+    ))(i)
 }
 
 /// A version of `tag` that uses our error type.
@@ -156,11 +232,18 @@ fn tag(val: &'static str) -> impl Fn(Span<'_>) -> IResult<Span<'_>> {
     }
 }
 
-/// A floating point number
+/// A floating point number.
+///
+/// The whole part can have commas separating groups of thousands (`1,234.5`). Only `.` is
+/// accepted as the decimal point, so European style numbers like `1.234,5` are an error.
 fn float(i: Span) -> IResult<f64> {
-    let (i, num) =
-        nom::number::complete::recognize_float(i).map_error(|_| ErrorKind::ExpectedFloat(None))?;
-    match num.parse::<f64>() {
+    let (i, num) = number(i).map_error(|_| ErrorKind::ExpectedFloat(None))?;
+    // a trailing comma means a badly placed separator or a decimal comma
+    if !thousands_grouped(num.fragment()) || i.starts_with(',') {
+        let kind = ErrorKind::ExpectedFloat(None);
+        return Err(nom::Err::Error(Error::new(&num, kind)));
+    }
+    match num.replace(',', "").parse::<f64>() {
         Ok(v) => Ok((i, v)),
         Err(e) => {
             let kind = ErrorKind::ExpectedFloat(Some(e));
@@ -169,6 +252,35 @@ fn float(i: Span) -> IResult<f64> {
     }
 }
 
+/// Recognize something that looks like a number, allowing commas in the whole part.
+fn number(i: Span) -> nom::IResult<Span, Span> {
+    let whole = recognize(pair(
+        separated_list1(char(','), digit1),
+        nom::combinator::opt(pair(char('.'), digit0)),
+    ));
+    let frac = recognize(pair(char('.'), digit1));
+    let exponent = tuple((one_of("eE"), nom::combinator::opt(one_of("+-")), digit1));
+    recognize(tuple((
+        nom::combinator::opt(one_of("+-")),
+        alt((whole, frac)),
+        nom::combinator::opt(exponent),
+    )))(i)
+}
+
+/// Check that any commas in a number are separating groups of 3 digits (so `1,234` but not
+/// `12,34`).
+fn thousands_grouped(num: &str) -> bool {
+    let whole = num
+        .trim_start_matches(['+', '-'])
+        .split(['.', 'e', 'E'])
+        .next()
+        .unwrap_or("");
+    let mut groups = whole.split(',');
+    let first = groups.next().unwrap_or("");
+    // without any commas there is nothing to check
+    whole == first || (first.len() <= 3 && groups.all(|group| group.len() == 3))
+}
+
 fn opt<'input, T, F: Fn(Span<'input>) -> IResult<'input, T>>(
     f: F,
 ) -> impl Fn(Span<'input>) -> IResult<'input, Option<T>> {
@@ -197,3 +309,122 @@ impl<'a, T> MapErr for nom::IResult<Span<'a>, T> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Result<Pie, Error> {
+        Pie::parse(src)
+    }
+
+    #[test]
+    fn title_without_data() {
+        let pie = parse("pie title My Chart").unwrap();
+        assert_eq!(pie.title, "My Chart");
+        assert!(pie.data.is_empty());
+
+        let pie = parse("pie title My Chart  \n%% nothing here yet\n").unwrap();
+        assert_eq!(pie.title, "My Chart");
+        assert!(pie.data.is_empty());
+    }
+
+    #[test]
+    fn title_runs_to_end_of_line() {
+        let pie = parse("pie title A \"quoted\" title\n\"a\": 1").unwrap();
+        assert_eq!(pie.title, "A \"quoted\" title");
+        assert_eq!(pie.data.len(), 1);
+        assert_eq!(pie.data[0].label, "a");
+    }
+
+    #[test]
+    fn negative_value() {
+        let err = parse("pie\n\"a\": 1\n\"b\" : -2.5").unwrap_err();
+        match err.kind() {
+            ErrorKind::NegativeValue(label) => assert_eq!(label, "b"),
+            other => panic!("expected NegativeValue, got {:?}", other),
+        }
+        // points at the value
+        assert_eq!((err.line, err.col), (3, 7));
+        assert_eq!(
+            err.to_string(),
+            "on line 3, col 7: data point \"b\" has a negative value"
+        );
+
+        // zero is fine
+        assert!(parse("pie\n\"a\": 0\n\"b\": 1").is_ok());
+    }
+
+    #[test]
+    fn require_data() {
+        let options = PieParseOptions {
+            require_data: true,
+            ..PieParseOptions::default()
+        };
+        let err = Pie::parse_with_options("pie title Nothing yet\n", &options).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NoData), "{:?}", err);
+        let err = Pie::parse_with_options("pie\n\"a\": 0\n\"b\": 0", &options).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::ZeroTotal), "{:?}", err);
+        assert!(Pie::parse_with_options("pie\n\"a\": 0\n\"b\": 1", &options).is_ok());
+
+        // by default, there just isn't anything to draw
+        assert!(parse("pie").unwrap().data.is_empty());
+        assert_eq!(parse("pie\n\"a\": 0\n\"b\": 0").unwrap().data.len(), 2);
+    }
+
+    #[test]
+    fn trim_labels() {
+        let src = "pie title  Padded  \n\"  Dogs \": 1";
+        let pie = parse(src).unwrap();
+        assert_eq!(pie.data[0].label, "Dogs");
+        assert_eq!(pie.title, "Padded");
+
+        let options = PieParseOptions {
+            trim_labels: false,
+            ..PieParseOptions::default()
+        };
+        let pie = Pie::parse_with_options(src, &options).unwrap();
+        assert_eq!(pie.data[0].label, "  Dogs ");
+        // the title is always trimmed
+        assert_eq!(pie.title, "Padded");
+    }
+
+    #[test]
+    fn unicode_columns() {
+        let pie = parse("pie title Haustiere 🐶\n\"Hunde 🐕\": 3").unwrap();
+        assert_eq!(pie.title, "Haustiere 🐶");
+        assert_eq!(pie.data[0].label, "Hunde 🐕");
+
+        // the column counts characters, not bytes
+        let err = parse("pie\n\"Hunde 🐕\": x").unwrap_err();
+        assert_eq!((err.line, err.col), (2, 12));
+    }
+
+    #[test]
+    fn thousands_separators() {
+        for (src, value) in [
+            ("1,234.5", 1234.5),
+            ("1234.5", 1234.5),
+            ("12,345,678", 12345678.),
+            ("-1,000", -1000.),
+            ("999", 999.),
+        ] {
+            let (_, v) = float(Span::new(src)).unwrap();
+            assert_eq!(v, value, "{}", src);
+        }
+        let a = parse("pie\n\"a\": 1,234.5").unwrap();
+        let b = parse("pie\n\"a\": 1234.5").unwrap();
+        assert_eq!(a.data[0].value, b.data[0].value);
+
+        // badly placed separators, and European style decimal commas
+        for src in ["12,34", "1,2345", ",123", "1.234,5", "1,234,"] {
+            let err = parse(&format!("pie\n\"a\": {}", src)).unwrap_err();
+            assert!(
+                matches!(err.kind(), ErrorKind::ExpectedFloat(_)),
+                "{}: {:?}",
+                src,
+                err.kind()
+            );
+        }
+    }
+}
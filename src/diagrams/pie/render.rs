@@ -1,125 +1,404 @@
-use super::{Pie, PieStyle, TextStyle};
-use anyhow::Result;
-use kurbo::{Affine, CircleSegment, Point, Rect, Size};
-use piet::{RenderContext, Text, TextLayout, TextLayoutBuilder};
-use std::{
-    f64::consts::{FRAC_PI_2, PI},
-    sync::Arc,
+use super::{
+    Datum, ErrorKind, Pie, PieSort, PieStyle, RenderParts, SegmentGeometry, SegmentLabelContent,
+    Side,
+};
+#[cfg(feature = "svg")]
+use crate::diagrams::svg::Tooltip;
+use crate::{
+    style::{clear_background, ApplyStyle, Pattern, PixelGrid, TextMeasurer},
+    text::label_text,
 };
+use anyhow::Result;
+#[cfg(feature = "svg")]
+use kurbo::Shape;
+use kurbo::{Affine, CircleSegment, Point, Rect, Size, Vec2};
+use piet::{RenderContext, Text, TextAlignment, TextLayout, TextLayoutBuilder};
+use std::f64::consts::{FRAC_PI_2, PI};
 
-const PIE_RADIUS: f64 = 100.;
-const STROKE_THICKNESS: f64 = 1.5;
-const PADDING: f64 = 5.;
+/// The angle the first segment starts at (straight up).
+const START_ANGLE: f64 = -FRAC_PI_2;
 
+/// Render the chart, using `text` to lay out any text.
 pub fn render<RC: RenderContext>(
     chart: &Pie,
     style: &PieStyle,
     ctx: &mut RC,
+    text: &mut RC::Text,
 ) -> Result<(), piet::Error> {
-    // build text layouts
-    let title_layout = if chart.title.is_empty() {
-        None
-    } else {
-        let title: Arc<str> = chart.title.into();
-
-        Some(
-            ctx.text()
-                .new_text_layout(title)
-                .apply_style(&style.title)
-                .build()?,
-        )
-    };
-    let legend = Legend::build(chart, style, ctx)?;
+    clear_background(ctx, style.background_color);
+    render_parts(chart, RenderParts::ALL, style, ctx, text)
+}
+
+/// Render the selected parts of the chart, without clearing the background.
+pub fn render_parts<RC: RenderContext>(
+    chart: &Pie,
+    parts: RenderParts,
+    style: &PieStyle,
+    ctx: &mut RC,
+    text: &mut RC::Text,
+) -> Result<(), piet::Error> {
+    if parts.pie {
+        check_data(chart)?;
+    }
+    let data = chart_data(chart, style);
+    let layout = ChartLayout::build(&data, chart, parts, style, text)?;
 
     // build brushes
     let stroke_brush = ctx.solid_brush(style.segment_outline.color);
-    let color_brushes = (0..chart.data.len())
+    let color_brushes = (0..data.len())
         .map(|idx| {
             let color = style.segment_colors.color(idx);
             ctx.solid_brush(color)
         })
         .collect::<Vec<_>>();
 
-    ctx.clear(None, style.background_color);
-
     // draw title
-    if let Some(ref layout) = title_layout {
-        let size = layout.size();
-        let title_tl = Point {
-            x: PIE_RADIUS - size.width * 0.5 + 10.,
-            y: 10.,
-        };
-        ctx.draw_text(&layout, title_tl);
+    if let Some((ref title, _, title_tl)) = layout.title {
+        let title = text
+            .new_text_layout(title.clone())
+            .apply_style(&style.title)
+            .alignment(TextAlignment::Center)
+            .build()?;
+        ctx.draw_text(&title, title_tl);
     }
 
     // draw chart
-    let y_offset = match title_layout {
-        Some(layout) => layout.size().height + 2. * 10.,
-        None => 10.,
-    };
-    ctx.with_save(|ctx| {
-        ctx.transform(Affine::translate((10., y_offset)));
-        draw_pie(chart, style, ctx, &stroke_brush, &color_brushes[..])
-    })?;
+    if parts.pie {
+        ctx.with_save(|ctx| {
+            ctx.transform(Affine::translate(layout.pie_origin.to_vec2()));
+            draw_pie(&data, style, ctx, text, &stroke_brush, &color_brushes[..])
+        })?;
+    }
 
     // draw legend
-    ctx.with_save(|ctx| {
-        let x_offset = PIE_RADIUS * 2. + 20. + 40.;
-        let y_offset = y_offset + PIE_RADIUS - legend.size().height * 0.5;
-        ctx.transform(Affine::translate((x_offset, y_offset)));
-        legend.render(ctx, style, &stroke_brush, &color_brushes[..])
-    })
+    if let Some(ref legend) = layout.legend {
+        ctx.with_save(|ctx| {
+            ctx.transform(Affine::translate(layout.legend_origin.to_vec2()));
+            legend.render(ctx, text, style, &stroke_brush, &color_brushes[..])
+        })?;
+    }
+    Ok(())
+}
+
+/// Calculate the size of the canvas needed to draw the selected parts of the chart.
+pub fn measure(
+    chart: &Pie,
+    parts: RenderParts,
+    style: &PieStyle,
+    text: &mut dyn TextMeasurer,
+) -> Result<Size, piet::Error> {
+    let data = chart_data(chart, style);
+    Ok(ChartLayout::build(&data, chart, parts, style, text)?.size)
+}
+
+/// Calculate where each segment of the pie is drawn, in the coordinates used by [`render`].
+pub fn segment_geometry<'a>(
+    chart: &Pie<'a>,
+    style: &PieStyle,
+    text: &mut dyn TextMeasurer,
+) -> Result<Vec<SegmentGeometry<'a>>, piet::Error> {
+    let data = chart_data(chart, style);
+    let layout = ChartLayout::build(&data, chart, RenderParts::ALL, style, text)?;
+    let center = layout.pie_origin + Vec2::new(style.radius, style.radius);
+
+    let mut segment_start = START_ANGLE;
+    let geometry = data
+        .iter()
+        .zip(proportions(&data))
+        .map(|(datum, proportion)| {
+            let sweep_angle = PI * 2. * proportion;
+            let segment = SegmentGeometry {
+                label: datum.label,
+                value: datum.value,
+                start_angle: segment_start,
+                sweep_angle,
+                center,
+                radius: style.radius,
+                inner_radius: style.radius * style.inner_radius_ratio,
+            };
+            segment_start += sweep_angle;
+            segment
+        })
+        .collect();
+    Ok(geometry)
+}
+
+/// Get the area of each segment and legend entry, along with a description of the data point
+/// it shows, in the coordinates used by [`render`].
+#[cfg(feature = "svg")]
+pub fn tooltips<RC: RenderContext>(
+    chart: &Pie,
+    style: &PieStyle,
+    ctx: &mut RC,
+) -> Result<Vec<Tooltip>, piet::Error> {
+    let data = chart_data(chart, style);
+    let mut text = ctx.text().clone();
+    let layout = ChartLayout::build(&data, chart, RenderParts::ALL, style, &mut text)?;
+    let proportions = proportions(&data);
+    let descriptions = data
+        .iter()
+        .zip(rounded_percentages(&proportions))
+        .map(|(datum, percentage)| {
+            let label = label_text(datum.label);
+            format!(
+                "{}: {} ({}%)",
+                label,
+                style.format_value(datum.value),
+                percentage
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut tooltips = vec![];
+    let mut segment_start = START_ANGLE;
+    for (proportion, description) in proportions.iter().zip(&descriptions) {
+        let segment = CircleSegment {
+            center: layout.pie_origin + Vec2::new(style.radius, style.radius),
+            outer_radius: style.radius,
+            inner_radius: style.radius * style.inner_radius_ratio,
+            start_angle: segment_start,
+            sweep_angle: PI * 2. * proportion,
+        };
+        tooltips.push(Tooltip {
+            shape: segment.to_path(0.1),
+            text: description.clone(),
+            id: None,
+        });
+        segment_start += segment.sweep_angle;
+    }
+    if let Some(legend) = layout.legend {
+        let offset = layout.legend_origin.to_vec2();
+        for (row, description) in legend.rows(style).zip(descriptions) {
+            tooltips.push(Tooltip {
+                shape: (row + offset).to_path(0.1),
+                text: description,
+                id: None,
+            });
+        }
+    }
+    Ok(tooltips)
+}
+
+/// Where each part of the chart should be drawn.
+struct ChartLayout {
+    /// The title text, its size and its top-left corner.
+    title: Option<(String, Size, Point)>,
+    /// The top-left corner of the pie.
+    pie_origin: Point,
+    legend: Option<Legend>,
+    /// The top-left corner of the legend.
+    legend_origin: Point,
+    /// The size of the canvas needed to fit everything in.
+    size: Size,
+}
+
+impl ChartLayout {
+    fn build(
+        data: &[Datum],
+        chart: &Pie,
+        parts: RenderParts,
+        style: &PieStyle,
+        text: &mut dyn TextMeasurer,
+    ) -> Result<Self, piet::Error> {
+        // measure text
+        let title = if !parts.title || chart.title.is_empty() {
+            None
+        } else {
+            let title = label_text(chart.title).into_owned();
+            let size = text.measure(&title, &style.title)?;
+            // the title is centered over the pie, if there is one
+            let title_x = if parts.pie {
+                style.margin + style.radius - size.width * 0.5
+            } else {
+                style.margin
+            };
+            Some((title, size, Point::new(title_x, style.margin)))
+        };
+        let legend = if parts.legend {
+            Some(Legend::build(data, chart.show_data, style, text)?)
+        } else {
+            None
+        };
+
+        let pie_origin = match title {
+            Some((_, size, _)) => {
+                Point::new(style.margin, style.margin + size.height + style.title_gap)
+            }
+            None => Point::new(style.margin, style.margin),
+        };
+        // the legend sits to the right of the pie, vertically centered, or takes its place
+        let legend_origin = match (&legend, parts.pie) {
+            (Some(legend), true) => Point {
+                x: pie_origin.x + style.radius * 2. + style.legend_gap,
+                y: pie_origin.y + style.radius - legend.size().height * 0.5,
+            },
+            _ => pie_origin,
+        };
+
+        // the canvas reaches from (0, 0) to the bottom-right of everything we draw, plus a margin
+        let pie_size = Size::new(style.radius * 2., style.radius * 2.);
+        let bounds = [
+            parts
+                .pie
+                .then(|| Rect::from_origin_size(pie_origin, pie_size)),
+            legend
+                .as_ref()
+                .map(|legend| Rect::from_origin_size(legend_origin, legend.size())),
+            title
+                .as_ref()
+                .map(|(_, size, title_tl)| Rect::from_origin_size(*title_tl, *size)),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a.union(b));
+        let size = match bounds {
+            Some(bounds) => Size::new(bounds.x1 + style.margin, bounds.y1 + style.margin),
+            None => Size::ZERO,
+        };
+
+        Ok(ChartLayout {
+            title,
+            pie_origin,
+            legend,
+            legend_origin,
+            size,
+        })
+    }
+}
+
+/// Get the data from the chart in the order it should be drawn, with small values collapsed if
+/// requested.
+fn chart_data<'a>(chart: &Pie<'a>, style: &PieStyle) -> Vec<Datum<'a>> {
+    let mut data = chart.data.clone();
+    let mut other = None;
+    if let Some(threshold) = style.collapse_below {
+        let total: f64 = data.iter().map(|d| d.value).sum();
+        let is_small = |d: &Datum| d.value < total * threshold;
+        // there's no point collapsing a single value into "Other"
+        if data.iter().filter(|d| is_small(d)).count() > 1 {
+            other = Some(Datum {
+                label: "Other",
+                value: data.iter().filter(|d| is_small(d)).map(|d| d.value).sum(),
+            });
+            data.retain(|d| !is_small(d));
+        }
+    }
+
+    match style.sort {
+        PieSort::None => (),
+        PieSort::Descending => data.sort_by(|a, b| b.value.total_cmp(&a.value)),
+        PieSort::Ascending => data.sort_by(|a, b| a.value.total_cmp(&b.value)),
+    }
+    data.extend(other);
+    data
+}
+
+/// Check that the pie has some size, since each segment is sized by its share of the total.
+///
+/// Fails with [`ErrorKind::NoData`] or [`ErrorKind::ZeroTotal`] if there's nothing to draw.
+fn check_data(chart: &Pie) -> Result<(), piet::Error> {
+    let kind = if chart.data.is_empty() {
+        ErrorKind::NoData
+    } else if chart.data.iter().all(|datum| datum.value == 0.) {
+        ErrorKind::ZeroTotal
+    } else {
+        return Ok(());
+    };
+    Err(piet::Error::BackendError(Box::new(kind)))
 }
 
 /// Draw the actual pie shape with inner labels at (0, 0).
 fn draw_pie<RC: RenderContext>(
-    chart: &Pie,
+    data: &[Datum],
     style: &PieStyle,
     ctx: &mut RC,
+    text: &mut RC::Text,
     stroke_brush: &RC::Brush,
     color_brushes: &[RC::Brush],
 ) -> Result<(), piet::Error> {
-    let total: f64 = chart.data.iter().map(|d| d.value).sum();
+    let proportions = proportions(data);
+    let percentages = rounded_percentages(&proportions);
     // the angle to start the segment at
-    let mut segment_start = -FRAC_PI_2;
+    let mut segment_start = START_ANGLE;
 
-    let pie_center = Point::from((PIE_RADIUS, PIE_RADIUS));
-    let pie_radius = PIE_RADIUS;
+    let pie_center = Point::from((style.radius, style.radius));
+    let pie_radius = style.radius;
+    let inner_radius = pie_radius * style.inner_radius_ratio;
+    // labels go half way between the inner and outer edges, so they stay within a donut's ring
+    let label_radius = (pie_radius + inner_radius) * 0.5;
+    let segment_stroke_style = style.segment_outline.piet_style();
+    let grid = style
+        .snap_to_pixels
+        .then(|| PixelGrid::new(ctx.current_transform()));
+    let stroke_width = |width: f64| match grid {
+        Some(grid) => grid.stroke_width(width),
+        None => width,
+    };
 
-    for (datum, brush) in chart.data.iter().zip(color_brushes) {
-        let proportion = datum.value / total;
+    for (idx, (&proportion, brush)) in proportions.iter().zip(color_brushes).enumerate() {
         // draw segment
         let segment_sweep = PI * 2. * proportion;
         let segment = CircleSegment {
             center: pie_center,
             outer_radius: pie_radius,
-            inner_radius: 0.,
+            inner_radius,
             start_angle: segment_start,
             sweep_angle: segment_sweep,
         };
-        ctx.fill(&segment, brush);
-        ctx.stroke(&segment, stroke_brush, style.segment_outline.width);
+        if style.segment_fill {
+            ctx.fill(&segment, brush);
+        }
+        segment_pattern(style, idx).draw(ctx, &segment, stroke_brush)?;
+        match style.segment_stroke_overrides.get(&idx) {
+            Some(outline) => {
+                let outline_brush = ctx.solid_brush(outline.color);
+                ctx.stroke_styled(
+                    &segment,
+                    &outline_brush,
+                    stroke_width(outline.width),
+                    &outline.piet_style(),
+                );
+            }
+            None => ctx.stroke_styled(
+                &segment,
+                stroke_brush,
+                stroke_width(style.segment_outline.width),
+                &segment_stroke_style,
+            ),
+        }
 
-        if let Some(ref label_style) = style.segment_label {
+        let content = match style.segment_label_content {
+            SegmentLabelContent::Percent => Some(format!("{}%", percentages[idx])),
+            SegmentLabelContent::Value => Some(style.format_value(data[idx].value)),
+            SegmentLabelContent::LabelAndPercent => Some(format!(
+                "{}\n{}%",
+                label_text(data[idx].label),
+                percentages[idx]
+            )),
+            SegmentLabelContent::None => None,
+        };
+        // labels on thin segments would spill over their neighbours
+        let content = content.filter(|_| segment_sweep >= style.min_label_sweep);
+        if let (Some(label_style), Some(content)) = (&style.segment_label, content) {
             // layout label
-            let percentage_layout = ctx
-                .text()
-                .new_text_layout(format!("{:.0}%", proportion * 100.))
+            let label_layout = text
+                .new_text_layout(content)
                 .apply_style(label_style)
+                .alignment(TextAlignment::Center)
                 .build()?;
-            let layout_size = percentage_layout.size();
+            let layout_size = label_layout.size();
 
             // draw label
             let segment_center = segment_start + segment_sweep * 0.5;
             let label_center = Point {
-                x: pie_center.x + segment_center.cos() * pie_radius * 0.5,
-                y: pie_center.y + segment_center.sin() * pie_radius * 0.5,
+                x: pie_center.x + segment_center.cos() * label_radius,
+                y: pie_center.y + segment_center.sin() * label_radius,
             };
             let label_tl = Point {
                 x: label_center.x - layout_size.width * 0.5,
                 y: label_center.y - layout_size.height * 0.5,
             };
-            ctx.draw_text(&percentage_layout, label_tl);
+            ctx.draw_text(&label_layout, label_tl);
         }
 
         segment_start += segment_sweep;
@@ -128,93 +407,473 @@ fn draw_pie<RC: RenderContext>(
     Ok(())
 }
 
-struct Legend<RC: RenderContext> {
-    layouts: Vec<RC::TextLayout>,
+/// The hatching pattern to draw over the segment with the given index.
+fn segment_pattern(style: &PieStyle, idx: usize) -> Pattern {
+    match style.segment_patterns {
+        Some(ref patterns) => patterns.pattern(idx),
+        None => Pattern::None,
+    }
+}
+
+/// The fraction of the whole pie taken up by each value.
+///
+/// If there's nothing to draw (all the values are zero), every proportion is zero.
+fn proportions(data: &[Datum]) -> Vec<f64> {
+    let total: f64 = data.iter().map(|d| d.value).sum();
+    if total == 0. {
+        return vec![0.; data.len()];
+    }
+    data.iter().map(|d| d.value / total).collect()
+}
+
+/// Round proportions (which should add up to 1) to whole percentages that add up to exactly 100.
+///
+/// This uses the largest remainder method: everything is rounded down, then the percentage points
+/// left over go to the values that lost the most by rounding.
+fn rounded_percentages(proportions: &[f64]) -> Vec<u32> {
+    if proportions.iter().all(|&p| p == 0.) {
+        // nothing to share out
+        return vec![0; proportions.len()];
+    }
+    let exact = proportions.iter().map(|p| p * 100.).collect::<Vec<_>>();
+    let mut rounded = exact.iter().map(|p| p.floor() as u32).collect::<Vec<_>>();
+
+    let leftover = 100u32.saturating_sub(rounded.iter().sum());
+    let mut by_remainder = (0..exact.len()).collect::<Vec<_>>();
+    by_remainder.sort_by(|&a, &b| {
+        let remainder = |idx: usize| exact[idx] - exact[idx].floor();
+        remainder(b).total_cmp(&remainder(a))
+    });
+    for idx in by_remainder.into_iter().take(leftover as usize) {
+        rounded[idx] += 1;
+    }
+    rounded
+}
+
+struct Legend {
+    /// The label for each entry, and its size.
+    labels: Vec<(String, Size)>,
     size: Size,
 }
 
-impl<RC: RenderContext> Legend<RC> {
-    fn build(pie: &Pie, style: &PieStyle, ctx: &mut RC) -> Result<Self, piet::Error> {
-        let layouts = pie
-            .data
+impl Legend {
+    fn build(
+        data: &[Datum],
+        show_data: bool,
+        style: &PieStyle,
+        text: &mut dyn TextMeasurer,
+    ) -> Result<Self, piet::Error> {
+        let labels = data
             .iter()
             .map(|datum| {
-                let text = if pie.show_data {
-                    format!("{} [{}]", datum.label, datum.value)
+                let label = label_text(datum.label);
+                let label = if show_data {
+                    format!("{} [{}]", label, style.format_value(datum.value))
                 } else {
-                    datum.label.to_string()
+                    label.into_owned()
                 };
-                Ok(ctx
-                    .text()
-                    .new_text_layout(text)
-                    .apply_style(&style.legend_label)
-                    .build()?)
+                let size = text.measure(&label, &style.legend_label)?;
+                Ok((label, size))
             })
             .collect::<Result<Vec<_>, piet::Error>>()?;
 
         // calculate size
+        let padding = style.legend_padding;
         let mut width: f64 = 0.;
-        for layout in &layouts {
-            let size = layout.size();
+        let mut height: f64 = 0.;
+        for &(_, size) in &labels {
             width = width.max(size.width);
+            height += row_height(size, style) + padding;
         }
         let size = Size {
-            // 10. for color + 3*10. for padding
-            width: width + style.legend_label.font_size + 3. * PADDING,
-            // (n+1) * 10. for padding
-            height: (style.legend_label.font_size + PADDING) * layouts.len() as f64 + PADDING,
+            // the color square, and padding either side of it and the label
+            width: width + style.legend_label.font_size + 3. * padding,
+            // padding between each row, and above the first one
+            height: height + padding,
         };
 
-        Ok(Legend { layouts, size })
+        Ok(Legend { labels, size })
     }
 
     fn size(&self) -> Size {
         self.size
     }
 
-    fn render(
+    fn render<RC: RenderContext>(
         &self,
         ctx: &mut RC,
+        text: &mut RC::Text,
         style: &PieStyle,
         stroke_brush: &RC::Brush,
         color_brushes: &[RC::Brush],
     ) -> Result<(), piet::Error> {
         let color_width = style.legend_label.font_size;
+        let padding = style.legend_padding;
+        let stroke_style = style.segment_outline.piet_style();
+        let grid = style
+            .snap_to_pixels
+            .then(|| PixelGrid::new(ctx.current_transform()));
+        let stroke_width = match grid {
+            Some(grid) => grid.stroke_width(style.legend_outline_width),
+            None => style.legend_outline_width,
+        };
+        let snap = |rect: Rect| match grid {
+            Some(grid) => grid.stroke_rect(rect, stroke_width),
+            None => rect,
+        };
 
         // draw outline
-        let outline = self.size.to_rect();
-        ctx.stroke(outline, stroke_brush, STROKE_THICKNESS);
+        let outline = snap(self.size.to_rect());
+        ctx.stroke_styled(outline, stroke_brush, stroke_width, &stroke_style);
 
-        let mut top = PADDING;
-        for (layout, brush) in self.layouts.iter().zip(color_brushes) {
-            let color_sq_tl = Point::new(PADDING, top);
+        // x positions of the color square and the label
+        let (color_sq_x, label_x) = match style.legend_marker_side {
+            Side::Before => (padding, 2. * padding + color_width),
+            // swatches are right-aligned so they line up
+            Side::After => (self.size.width - padding - color_width, padding),
+        };
+
+        let rows = self.labels.iter().zip(color_brushes).zip(self.rows(style));
+        for (idx, (((label, _), brush), row)) in rows.enumerate() {
+            let color_sq_tl = Point::new(color_sq_x, row.y0);
             let color_sq_sz = Size::new(color_width, color_width);
-            let color_square = Rect::from_origin_size(color_sq_tl, color_sq_sz);
-            ctx.stroke(color_square, stroke_brush, STROKE_THICKNESS);
+            let color_square = snap(Rect::from_origin_size(color_sq_tl, color_sq_sz));
+            ctx.stroke_styled(color_square, stroke_brush, stroke_width, &stroke_style);
             ctx.fill(color_square, brush);
-            ctx.draw_text(layout, Point::new(2. * PADDING + color_width, top));
-            top += color_width + PADDING;
+            segment_pattern(style, idx).draw(ctx, color_square, stroke_brush)?;
+            let layout = text
+                .new_text_layout(label.clone())
+                .apply_style(&style.legend_label)
+                .build()?;
+            ctx.draw_text(&layout, Point::new(label_x, row.y0));
         }
 
         Ok(())
     }
+
+    /// The area taken up by each entry of the legend.
+    fn rows<'a>(&'a self, style: &'a PieStyle) -> impl Iterator<Item = Rect> + 'a {
+        let mut top = style.legend_padding;
+        self.labels.iter().map(move |&(_, size)| {
+            let height = row_height(size, style);
+            let row = Rect::new(0., top, self.size.width, top + height);
+            top += height + style.legend_padding;
+            row
+        })
+    }
 }
 
-trait ApplyStyle {
-    fn apply_style(self, style: &TextStyle) -> Self;
+/// The height of a legend row, which is at least the height of the color square but grows to fit
+/// labels spanning multiple lines.
+fn row_height(label_size: Size, style: &PieStyle) -> f64 {
+    label_size.height.max(style.legend_label.font_size)
 }
 
-impl<T: TextLayoutBuilder> ApplyStyle for T {
-    fn apply_style(self, style: &TextStyle) -> Self {
-        let mut this =
-            self.default_attribute(piet::TextAttribute::FontSize(px_to_pt(style.font_size)));
-        if style.bold {
-            this = this.default_attribute(piet::TextAttribute::Weight(piet::FontWeight::BOLD));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        style::{Color, NumberFormat, Side, StrokeStyle, TextStyle},
+        test_util::{FixedText, Recorder},
+    };
+
+    const PIE_ONLY: RenderParts = RenderParts {
+        pie: true,
+        legend: false,
+        title: false,
+    };
+
+    /// Draw the parts of a chart to a [`Recorder`].
+    fn record(src: &str, parts: RenderParts, style: &PieStyle) -> Recorder {
+        let pie = Pie::parse(src).unwrap();
+        let mut ctx = Recorder::new();
+        pie.render_parts(parts, style, &mut ctx).unwrap();
+        ctx
+    }
+
+    #[test]
+    fn nothing_to_draw() {
+        assert!(proportions(&[]).is_empty());
+        assert!(rounded_percentages(&[]).is_empty());
+
+        let zeros = [
+            Datum {
+                label: "a",
+                value: 0.,
+            },
+            Datum {
+                label: "b",
+                value: 0.,
+            },
+        ];
+        assert_eq!(proportions(&zeros), [0., 0.]);
+        assert_eq!(rounded_percentages(&proportions(&zeros)), [0, 0]);
+    }
+
+    /// Measures text as 10 by 10 per character, so tests don't need a piet backend.
+    struct FixedWidth;
+
+    impl TextMeasurer for FixedWidth {
+        fn measure(&mut self, text: &str, _: &TextStyle) -> Result<Size, piet::Error> {
+            Ok(Size::new(text.chars().count() as f64 * 10., 10.))
         }
-        this.text_color(style.color)
     }
-}
 
-fn px_to_pt(px: f64) -> f64 {
-    0.75 * px
+    #[test]
+    fn legend_values() {
+        let data = [
+            Datum {
+                label: "Dogs",
+                value: 5.,
+            },
+            Datum {
+                label: "Cats",
+                value: 1234.5,
+            },
+        ];
+        let labels = |style: &PieStyle| {
+            let legend = Legend::build(&data, true, style, &mut FixedWidth).unwrap();
+            legend
+                .labels
+                .into_iter()
+                .map(|(label, _)| label)
+                .collect::<Vec<_>>()
+        };
+
+        // the default is the same as writing out the value with `Display`
+        let expected = data
+            .iter()
+            .map(|datum| format!("{} [{}]", datum.label, datum.value))
+            .collect::<Vec<_>>();
+        assert_eq!(labels(&PieStyle::default()), expected);
+        let style = PieStyle::default().with_number_format(NumberFormat::new());
+        assert_eq!(labels(&style), expected);
+
+        let format = NumberFormat::new()
+            .with_decimals(1)
+            .with_thousands_separator(',')
+            .with_suffix(" kg");
+        let style = PieStyle::default().with_number_format(format);
+        assert_eq!(labels(&style), ["Dogs [5.0 kg]", "Cats [1,234.5 kg]"]);
+
+        // values are left out unless `showData` is set
+        let legend = Legend::build(&data, false, &style, &mut FixedWidth).unwrap();
+        assert_eq!(legend.labels[0].0, "Dogs");
+    }
+
+    #[test]
+    fn segment_stroke_override() {
+        let red = Color::rgb8(0xff, 0, 0);
+        let mut style = PieStyle::default();
+        style
+            .segment_stroke_overrides
+            .insert(1, StrokeStyle::new(3., red));
+        // there's no segment 7, so this is ignored
+        style
+            .segment_stroke_overrides
+            .insert(7, StrokeStyle::new(5., red));
+        let ctx = record("pie\n\"a\": 1\n\"b\": 2\n\"c\": 3", PIE_ONLY, &style);
+
+        let strokes = ctx.strokes();
+        let fills = ctx.fills();
+        assert_eq!(strokes.len(), 3);
+        // only the second segment has the thick red outline
+        let (shape, color, width) = strokes[1];
+        assert_eq!((color, width), (red, 3.));
+        assert_eq!(shape, fills[1].0);
+        for idx in [0, 2] {
+            let (_, color, width) = strokes[idx];
+            assert_eq!((color, width), (Color::BLACK, 1.5));
+        }
+    }
+
+    #[test]
+    fn legend_marker_side() {
+        let data = [
+            Datum {
+                label: "Dogs",
+                value: 5.,
+            },
+            Datum {
+                label: "Rabbits",
+                value: 3.,
+            },
+        ];
+        let swatches_and_labels = |side: Side| {
+            let style = PieStyle::default().with_legend_marker_side(side);
+            let legend = Legend::build(&data, false, &style, &mut FixedWidth).unwrap();
+            let mut ctx = Recorder::new();
+            let mut text = ctx.text().clone();
+            let stroke = ctx.solid_brush(Color::BLACK);
+            let colors = [Color::rgb8(0xff, 0, 0), Color::rgb8(0, 0, 0xff)]
+                .map(|color| ctx.solid_brush(color));
+            legend
+                .render(&mut ctx, &mut text, &style, &stroke, &colors)
+                .unwrap();
+            let swatches = ctx
+                .fills()
+                .into_iter()
+                .map(|(shape, _)| shape.bounding_box())
+                .collect::<Vec<_>>();
+            let labels = legend.labels.iter().map(|(_, size)| *size);
+            let labels = ctx
+                .texts()
+                .into_iter()
+                .zip(labels)
+                .map(|(pos, size)| Rect::from_origin_size(pos, size))
+                .collect::<Vec<_>>();
+            (swatches, labels)
+        };
+
+        let (swatches, labels) = swatches_and_labels(Side::Before);
+        assert_eq!(swatches.len(), 2);
+        for (swatch, label) in swatches.iter().zip(&labels) {
+            assert!(swatch.x1 <= label.x0);
+        }
+
+        let (swatches, labels) = swatches_and_labels(Side::After);
+        for (swatch, label) in swatches.iter().zip(&labels) {
+            assert!(swatch.x0 >= label.x1);
+        }
+        // the swatches line up, even though the labels are different lengths
+        assert_eq!(swatches[0].x0, swatches[1].x0);
+    }
+
+    #[test]
+    fn percentages_add_up_to_100() {
+        let thirds = [1. / 3.; 3];
+        assert_eq!(rounded_percentages(&thirds), [34, 33, 33]);
+
+        let sevenths = [1. / 7.; 7];
+        assert_eq!(rounded_percentages(&sevenths), [15, 15, 14, 14, 14, 14, 14]);
+
+        // the leftover goes to the value that lost the most by rounding down
+        let uneven = [1. / 7., 2. / 7., 4. / 7.];
+        assert_eq!(rounded_percentages(&uneven), [14, 29, 57]);
+
+        // already whole percentages are left alone
+        assert_eq!(rounded_percentages(&[0.5, 0.25, 0.25]), [50, 25, 25]);
+    }
+
+    #[test]
+    fn outlined_segments() {
+        let src = "pie\n\"a\": 1\n\"b\": 2\n\"c\": 3";
+        let ctx = record(src, PIE_ONLY, &PieStyle::default());
+        assert_eq!(ctx.fills().len(), 3);
+        assert_eq!(ctx.strokes().len(), 3);
+
+        let style = PieStyle::default().with_segment_fill(false);
+        let ctx = record(src, PIE_ONLY, &style);
+        assert!(ctx.fills().is_empty());
+        assert_eq!(ctx.strokes().len(), 3);
+
+        // the legend still shows the colors
+        let parts = RenderParts {
+            pie: false,
+            legend: true,
+            title: false,
+        };
+        let ctx = record(src, parts, &style);
+        assert_eq!(ctx.fills().len(), 3);
+    }
+
+    #[test]
+    fn nothing_to_draw_is_an_error() {
+        let render = |src: &str, parts: RenderParts| {
+            let pie = Pie::parse(src).unwrap();
+            pie.render_parts(parts, &PieStyle::default(), &mut Recorder::new())
+        };
+        let kind = |result: Result<(), piet::Error>| match result {
+            Err(piet::Error::BackendError(e)) => e.to_string(),
+            other => panic!("expected a backend error, got {:?}", other),
+        };
+        assert_eq!(
+            kind(render("pie title Coming soon", RenderParts::ALL)),
+            ErrorKind::NoData.to_string()
+        );
+        assert_eq!(
+            kind(render("pie\n\"a\": 0\n\"b\": 0", RenderParts::ALL)),
+            ErrorKind::ZeroTotal.to_string()
+        );
+        // the title and legend can still be drawn on their own
+        let parts = RenderParts {
+            pie: false,
+            ..RenderParts::ALL
+        };
+        assert!(render("pie title Coming soon", parts).is_ok());
+        assert!(render("pie\n\"a\": 1\n\"b\": 0", RenderParts::ALL).is_ok());
+        // and it's an error when exporting images too
+        #[cfg(feature = "svg")]
+        assert!(Pie::parse("pie").unwrap().to_svg_string(None).is_err());
+    }
+
+    #[test]
+    fn own_text_factory() {
+        let pie = Pie::parse("pie title Pets\n\"Dogs\": 3\n\"Cats\": 2").unwrap();
+        let style = PieStyle::default();
+        let mut ctx = Recorder::new();
+        pie.render_with_style(&style, &mut ctx).unwrap();
+
+        // a text factory that didn't come from the render context
+        let mut text = FixedText::default();
+        let mut own_text = Recorder::new();
+        pie.render_with_text(&style, &mut own_text, &mut text)
+            .unwrap();
+        assert_eq!(own_text.fills(), ctx.fills());
+        assert_eq!(own_text.strokes(), ctx.strokes());
+        assert_eq!(own_text.texts(), ctx.texts());
+        assert!(!ctx.texts().is_empty());
+    }
+
+    #[test]
+    fn render_parts_combinations() {
+        let src = "pie title Pets\n\"Dogs\": 3\n\"Cats\": 2\n\"Rats\": 1";
+        let chart = Pie::parse(src).unwrap();
+        let style = PieStyle::default();
+        for pie in [false, true] {
+            for legend in [false, true] {
+                for title in [false, true] {
+                    let parts = RenderParts { pie, legend, title };
+                    let ctx = record(src, parts, &style);
+                    let count = |part: bool, n: usize| if part { n } else { 0 };
+                    // a fill for each segment and legend swatch
+                    assert_eq!(ctx.fills().len(), count(pie, 3) + count(legend, 3));
+                    // a label on each segment and legend entry, and the title
+                    assert_eq!(
+                        ctx.texts().len(),
+                        count(pie, 3) + count(legend, 3) + count(title, 1),
+                        "{:?}",
+                        parts
+                    );
+                }
+            }
+        }
+
+        let size = |pie, legend, title| {
+            let parts = RenderParts { pie, legend, title };
+            chart.measure(parts, &style, &mut FixedWidth).unwrap()
+        };
+        let margins = Size::new(style.margin, style.margin) * 2.;
+        let pie_size = Size::new(style.radius, style.radius) * 2.;
+        let data = chart_data(&chart, &style);
+        let legend_size = Legend::build(&data, false, &style, &mut FixedWidth)
+            .unwrap()
+            .size();
+        let title_size = FixedWidth.measure("Pets", &style.title).unwrap();
+
+        assert_eq!(size(false, false, false), Size::ZERO);
+        assert_eq!(size(true, false, false), pie_size + margins);
+        assert_eq!(size(false, true, false), legend_size + margins);
+        assert_eq!(size(false, false, true), title_size + margins);
+        // the legend goes to the right of the pie
+        assert_eq!(
+            size(true, true, false).width,
+            pie_size.width + style.legend_gap + legend_size.width + margins.width
+        );
+        // and the title above it
+        assert_eq!(
+            size(true, false, true).height,
+            title_size.height + style.title_gap + pie_size.height + margins.height
+        );
+    }
 }
@@ -0,0 +1,74 @@
+use super::{Pie, PieParseOptions};
+use std::fmt;
+
+/// How far from 100 the values can add up to when they are meant to be percentages.
+///
+/// This allows for percentages that have been rounded (e.g. 3 × 33.3).
+const PERCENT_TOLERANCE: f64 = 1.;
+
+impl<'input> Pie<'input> {
+    /// Look for things that are allowed, but are probably mistakes.
+    ///
+    /// The chart can still be drawn whatever this returns. `options` should be the options the
+    /// chart was parsed with.
+    pub fn validate(&self, options: &PieParseOptions) -> Vec<Warning> {
+        let mut warnings = vec![];
+        if options.assume_percentages {
+            let total = self.data.iter().map(|d| d.value).sum::<f64>();
+            if (total - 100.).abs() > PERCENT_TOLERANCE {
+                warnings.push(Warning::PercentTotal(total));
+            }
+        }
+        warnings
+    }
+}
+
+/// Something [`Pie::validate`] found that is probably a mistake.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The values were meant to be percentages (see [`PieParseOptions::assume_percentages`]),
+    /// but they don't add up to 100 (the inner value is what they do add up to).
+    PercentTotal(f64),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::PercentTotal(total) => {
+                write!(f, "percentages add up to {} rather than 100", total)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percentages() -> PieParseOptions {
+        PieParseOptions {
+            assume_percentages: true,
+            ..PieParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn percent_total() {
+        let src = "pie\n\"a\": 50\n\"b\": 30\n\"c\": 10";
+        let pie = Pie::parse_with_options(src, &percentages()).unwrap();
+        assert_eq!(pie.validate(&percentages()), [Warning::PercentTotal(90.)]);
+        assert_eq!(
+            Warning::PercentTotal(90.).to_string(),
+            "percentages add up to 90 rather than 100"
+        );
+        // raw counts can add up to anything
+        assert!(pie.validate(&PieParseOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn rounded_percentages_are_fine() {
+        let src = "pie\n\"a\": 33.3\n\"b\": 33.3\n\"c\": 33.3";
+        let pie = Pie::parse_with_options(src, &percentages()).unwrap();
+        assert!(pie.validate(&percentages()).is_empty());
+    }
+}
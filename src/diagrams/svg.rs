@@ -0,0 +1,117 @@
+//! Post-processing for the svg output from `piet_svg`.
+
+use super::render_error;
+use kurbo::{BezPath, Size};
+use std::{fmt::Write, io};
+
+/// An area of an svg image with a description, added by [`insert_tooltips`].
+pub(crate) struct Tooltip {
+    /// The area the description is for.
+    pub shape: BezPath,
+    /// The description.
+    pub text: String,
+    /// The `id` to give the shape, if any.
+    ///
+    /// Characters that aren't allowed in an xml id are replaced with `_`.
+    pub id: Option<String>,
+}
+
+/// Render an svg image with `draw`, and write it to `writer` with the tooltips `draw` returns.
+pub(crate) fn write_svg(
+    mut writer: impl io::Write,
+    size: Size,
+    draw: impl FnOnce(&mut piet_svg::RenderContext) -> Result<Vec<Tooltip>, piet::Error>,
+) -> io::Result<()> {
+    let mut rc = piet_svg::RenderContext::new(size);
+    let tooltips = draw(&mut rc).map_err(render_error)?;
+    let mut svg = vec![];
+    rc.write(&mut svg)?;
+    let svg = String::from_utf8(svg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.write_all(insert_tooltips(&svg, &tooltips).as_bytes())
+}
+
+/// Add an invisible shape with a `<title>` on top of each area in `tooltips`.
+///
+/// Browsers show the title when hovering over the shape, and screen readers read it out. The
+/// shapes are drawn last so they are on top of everything else.
+pub(crate) fn insert_tooltips(svg: &str, tooltips: &[Tooltip]) -> String {
+    let end = svg.rfind("</svg>").unwrap_or(svg.len());
+    let mut out = String::with_capacity(svg.len());
+    out.push_str(&svg[..end]);
+    out.push_str("<g fill=\"black\" fill-opacity=\"0\">\n");
+    for tooltip in tooltips {
+        out.push_str("<path ");
+        if let Some(ref id) = tooltip.id {
+            write!(out, "id=\"{}\" ", sanitize_id(id)).unwrap();
+        }
+        writeln!(
+            out,
+            "d=\"{}\"><title>{}</title></path>",
+            tooltip.shape.to_svg(),
+            escape(&tooltip.text)
+        )
+        .unwrap();
+    }
+    out.push_str("</g>\n");
+    out.push_str(&svg[end..]);
+    out
+}
+
+/// Make `id` into a valid xml id, by replacing any characters that aren't allowed.
+fn sanitize_id(id: &str) -> String {
+    let mut out = id
+        .chars()
+        .map(|c| match c {
+            '-' | '_' | '.' => c,
+            c if c.is_alphanumeric() => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    // ids can't start with a digit, `-` or `.`
+    if !out.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Escape text for use in xml.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        assert_eq!(sanitize_id("node-A_1.b"), "node-A_1.b");
+        assert_eq!(sanitize_id("node-a b<c>"), "node-a_b_c_");
+        assert_eq!(sanitize_id("1st"), "_1st");
+        assert_eq!(sanitize_id(""), "_");
+    }
+
+    #[test]
+    fn tooltips_go_before_end() {
+        let tooltips = [Tooltip {
+            shape: BezPath::new(),
+            text: "a < b".into(),
+            id: Some("x y".into()),
+        }];
+        let svg = insert_tooltips("<svg></svg>", &tooltips);
+        assert!(svg.starts_with("<svg><g"));
+        assert!(svg.ends_with("</g>\n</svg>"));
+        assert!(svg.contains("<path id=\"x_y\" d=\"\"><title>a &lt; b</title></path>"));
+    }
+}
@@ -7,14 +7,19 @@
 //!   - Very forgiving grammars that uses backtracking where necessary to accept as many different
 //!   valid inputs as possible.
 //!   - Error messages that explain the problem and locate it in the input.
-//! - Optional rendering of charts using [`piet`](https://crates.io/crates/piet).
+//! - Optional rendering of charts using [`piet`](https://crates.io/crates/piet) (the `render`
+//!   feature, which is on by default).
 //!
 //! # Non-goals
 //!
 //!  - Exact 1-1 correspondence between accepted grammars of `mermaid.js` and this library.
 //!  - Exact 1-1 look of rendered charts between `mermaid.js` and this library.
 
+pub mod complexity;
 mod diagrams;
 pub mod style;
+#[cfg(all(test, feature = "render"))]
+mod test_util;
+pub mod text;
 
 pub use diagrams::*;
@@ -1,24 +1,84 @@
 //! Some shared code to support styling charts.
-use piet::Color;
+//!
+//! Without the `render` feature the styles used for drawing charts aren't available, and
+//! [`Color`] is a small stand-in for `piet::Color` rather than a re-export of it.
+#[cfg(feature = "render")]
+use crate::text::RichText;
+#[cfg(not(feature = "render"))]
+pub use color::{Color, ColorParseError};
+#[cfg(feature = "render")]
+pub use piet::{Color, ColorParseError, LineCap, LineJoin};
+
+#[cfg(not(feature = "render"))]
+mod color;
 
 /// How to style drawing the outline of a shape.
+#[cfg(feature = "render")]
 #[derive(Debug, Clone)]
 pub struct StrokeStyle {
     /// The width of the outline.
     pub width: f64,
     /// The color of the outline.
     pub color: Color,
-    // todo dashing/linecap/etc
+    /// How to draw the corners where two segments of the outline meet.
+    ///
+    /// The default is a miter join.
+    pub line_join: LineJoin,
+    /// How to draw the ends of open segments (and of each dash).
+    ///
+    /// The default is a butt cap.
+    pub line_cap: LineCap,
+    /// Alternating lengths of dashes and gaps, or `None` for a solid line.
+    pub dash: Option<Vec<f64>>,
 }
 
+#[cfg(feature = "render")]
 impl StrokeStyle {
     /// Helper to create a stroke style.
     pub fn new(width: f64, color: Color) -> Self {
-        Self { width, color }
+        Self {
+            width,
+            color,
+            line_join: LineJoin::Miter {
+                limit: LineJoin::DEFAULT_MITER_LIMIT,
+            },
+            line_cap: LineCap::Butt,
+            dash: None,
+        }
+    }
+
+    /// Set how corners of the outline should be drawn.
+    pub fn with_line_join(mut self, line_join: LineJoin) -> Self {
+        self.line_join = line_join;
+        self
+    }
+
+    /// Set how the ends of the outline should be drawn.
+    pub fn with_line_cap(mut self, line_cap: LineCap) -> Self {
+        self.line_cap = line_cap;
+        self
+    }
+
+    /// Draw the outline dashed, alternating dashes and gaps of the given lengths.
+    pub fn with_dash(mut self, dash: impl Into<Vec<f64>>) -> Self {
+        self.dash = Some(dash.into());
+        self
+    }
+
+    /// Get the parts of this style that piet handles separately from the width and color.
+    pub(crate) fn piet_style(&self) -> piet::StrokeStyle {
+        let mut style = piet::StrokeStyle::new()
+            .line_join(self.line_join)
+            .line_cap(self.line_cap);
+        if let Some(ref dash) = self.dash {
+            style.set_dash_pattern(dash.clone());
+        }
+        style
     }
 }
 
 /// How to style some text.
+#[cfg(feature = "render")]
 #[derive(Debug, Clone)]
 pub struct TextStyle {
     /// The text color.
@@ -27,14 +87,21 @@ pub struct TextStyle {
     pub font_size: f64,
     /// Whether text should be bold.
     pub bold: bool,
+    /// Whether text should be italic.
+    pub italic: bool,
+    /// The name of the font family to use, or `None` for the system default.
+    pub font_family: Option<String>,
 }
 
+#[cfg(feature = "render")]
 impl TextStyle {
     pub(crate) fn default() -> Self {
         Self {
             color: Color::BLACK,
             font_size: 16.,
             bold: false,
+            italic: false,
+            font_family: None,
         }
     }
 
@@ -43,6 +110,8 @@ impl TextStyle {
             color: Color::WHITE,
             font_size: 16.,
             bold: false,
+            italic: false,
+            font_family: None,
         }
     }
 
@@ -57,9 +126,367 @@ impl TextStyle {
         self.bold = bold;
         self
     }
+
+    /// Set whether the text should be italic.
+    pub fn with_italic(mut self, italic: bool) -> Self {
+        self.italic = italic;
+        self
+    }
+
+    /// Set the font family to use, by name.
+    ///
+    /// If the family isn't available, the backend will fall back to some other font.
+    pub fn with_font_family(mut self, font_family: impl Into<String>) -> Self {
+        self.font_family = Some(font_family.into());
+        self
+    }
+}
+
+/// Apply a [`TextStyle`] to a piet text layout.
+#[cfg(feature = "render")]
+pub(crate) trait ApplyStyle {
+    fn apply_style(self, style: &TextStyle) -> Self;
+}
+
+#[cfg(feature = "render")]
+impl<T: piet::TextLayoutBuilder> ApplyStyle for T {
+    fn apply_style(self, style: &TextStyle) -> Self {
+        let mut this =
+            self.default_attribute(piet::TextAttribute::FontSize(px_to_pt(style.font_size)));
+        if style.bold {
+            this = this.default_attribute(piet::TextAttribute::Weight(piet::FontWeight::BOLD));
+        }
+        if style.italic {
+            this = this.default_attribute(piet::TextAttribute::Style(piet::FontStyle::Italic));
+        }
+        if let Some(ref family) = style.font_family {
+            let family = piet::FontFamily::new_unchecked(family.as_str());
+            this = this.default_attribute(piet::TextAttribute::FontFamily(family));
+        }
+        this.text_color(style.color)
+    }
+}
+
+/// Works out how much space text will take up, so charts can be laid out without a render
+/// context.
+///
+/// Every [`piet::Text`] (e.g. from [`piet::RenderContext::text`]) is a text measurer. Other
+/// implementations could use approximate font metrics, for laying out charts where no piet
+/// backend is available. Charts are still drawn with the render context's text, so the
+/// measurements should match it closely.
+#[cfg(feature = "render")]
+pub trait TextMeasurer {
+    /// Get the size of `text` drawn using `style`.
+    ///
+    /// The text can contain newlines, which start new lines.
+    fn measure(&mut self, text: &str, style: &TextStyle) -> Result<kurbo::Size, piet::Error>;
+
+    /// Get the size of some formatted text drawn using `style`.
+    ///
+    /// By default the formatting is ignored, and the plain text is measured.
+    fn measure_rich(
+        &mut self,
+        text: &RichText,
+        style: &TextStyle,
+    ) -> Result<kurbo::Size, piet::Error> {
+        self.measure(&text.plain_text(), style)
+    }
+}
+
+#[cfg(feature = "render")]
+impl<T: piet::Text> TextMeasurer for T {
+    fn measure(&mut self, text: &str, style: &TextStyle) -> Result<kurbo::Size, piet::Error> {
+        use piet::{TextLayout, TextLayoutBuilder};
+
+        let layout = self
+            .new_text_layout(text.to_string())
+            .apply_style(style)
+            .build()?;
+        Ok(layout.size())
+    }
+
+    fn measure_rich(
+        &mut self,
+        text: &RichText,
+        style: &TextStyle,
+    ) -> Result<kurbo::Size, piet::Error> {
+        use piet::TextLayout;
+
+        Ok(rich_text_layout(self, text, style)?.size())
+    }
+}
+
+/// Lay out some formatted text, with `style` as the default for all of it.
+///
+/// Each line is centered within the width of the longest one.
+#[cfg(feature = "render")]
+pub(crate) fn rich_text_layout<T: piet::Text>(
+    text: &mut T,
+    rich: &RichText,
+    style: &TextStyle,
+) -> Result<T::TextLayout, piet::Error> {
+    use piet::{TextAlignment, TextAttribute, TextLayoutBuilder};
+
+    let mut builder = text
+        .new_text_layout(rich.plain_text())
+        .alignment(TextAlignment::Center)
+        .apply_style(style);
+    let mut start = 0;
+    for run in &rich.runs {
+        let range = start..start + run.text.len();
+        if run.bold {
+            builder = builder
+                .range_attribute(range.clone(), TextAttribute::Weight(piet::FontWeight::BOLD));
+        }
+        if run.italic {
+            builder = builder.range_attribute(range, TextAttribute::Style(piet::FontStyle::Italic));
+        }
+        start += run.text.len();
+    }
+    builder.build()
+}
+
+#[cfg(feature = "render")]
+fn px_to_pt(px: f64) -> f64 {
+    0.75 * px
+}
+
+/// Fill the whole canvas with `color`, unless it is fully transparent.
+///
+/// Clearing to a transparent color erases the canvas on bitmap backends, but does nothing on the
+/// svg backend, so it is skipped to make them agree: a transparent background always leaves the
+/// canvas as it was. The chart styles default to a transparent background, so exported png and
+/// svg images are transparent too. Use e.g. [`Color::WHITE`] for an opaque background.
+///
+/// Charts clear the background themselves when rendered whole, so this is only needed when
+/// drawing parts of charts (e.g. with [`Pie::render_parts`](crate::Pie::render_parts)).
+#[cfg(feature = "render")]
+pub fn clear_background(ctx: &mut impl piet::RenderContext, color: Color) {
+    if color.as_rgba8().3 > 0 {
+        ctx.clear(None, color);
+    }
+}
+
+/// Lines shapes up with the pixels of the device being drawn to, so straight lines look crisp in
+/// bitmaps rather than being smeared across two rows of pixels.
+#[cfg(feature = "render")]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PixelGrid {
+    /// Maps from drawing coordinates to device pixels.
+    transform: kurbo::Affine,
+}
+
+#[cfg(feature = "render")]
+impl PixelGrid {
+    /// Create a grid from the current transform of a render context.
+    pub(crate) fn new(transform: kurbo::Affine) -> Self {
+        Self { transform }
+    }
+
+    /// The number of device pixels per unit length.
+    fn scale(&self) -> f64 {
+        self.transform.determinant().abs().sqrt()
+    }
+
+    /// Round a stroke width to a whole number of device pixels (at least one).
+    pub(crate) fn stroke_width(&self, width: f64) -> f64 {
+        let scale = self.scale();
+        (width * scale).round().max(1.) / scale
+    }
+
+    /// Move the edges of `rect` so that a stroke of the given width (which should already have
+    /// been snapped with [`PixelGrid::stroke_width`]) covers whole device pixels.
+    pub(crate) fn stroke_rect(&self, rect: kurbo::Rect, width: f64) -> kurbo::Rect {
+        // a stroke an odd number of pixels wide is centered on the middle of a pixel
+        let offset = if (width * self.scale()).round() as i64 % 2 == 1 {
+            0.5
+        } else {
+            0.
+        };
+        let snap = |point: kurbo::Point| {
+            let device = self.transform * point;
+            let device = kurbo::Point::new(
+                (device.x - offset).round() + offset,
+                (device.y - offset).round() + offset,
+            );
+            self.transform.inverse() * device
+        };
+        kurbo::Rect::from_points(
+            snap(rect.origin()),
+            snap(kurbo::Point::new(rect.x1, rect.y1)),
+        )
+    }
+}
+
+/// Parse a color from a hex string like `#rgb`, `#rrggbb` or `#rrggbbaa`.
+///
+/// The leading `#` is optional.
+pub fn parse_hex_color(s: &str) -> Result<Color, ColorParseError> {
+    Color::from_hex_str(s.trim())
+}
+
+/// Write a color as a hex string, the opposite of [`parse_hex_color`].
+///
+/// The alpha channel is only included if the color isn't opaque.
+pub(crate) fn color_to_hex(color: Color) -> String {
+    match color.as_rgba8() {
+        (r, g, b, 255) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        (r, g, b, a) => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+    }
+}
+
+/// Create a color from hue, saturation and lightness.
+///
+/// The hue is in degrees, and wraps around (so `-60.` is the same as `300.`). Saturation and
+/// lightness go from `0.` to `1.`, and are clamped to that range.
+pub fn color_from_hsl(h: f64, s: f64, l: f64) -> Color {
+    let s = s.clamp(0., 1.);
+    let l = l.clamp(0., 1.);
+    let chroma = (1. - (2. * l - 1.).abs()) * s;
+    color_from_hue_chroma(h, chroma, l - chroma / 2.)
+}
+
+/// Create a color from hue, saturation and value (also called HSB).
+///
+/// The hue is in degrees, and wraps around (so `-60.` is the same as `300.`). Saturation and
+/// value go from `0.` to `1.`, and are clamped to that range.
+pub fn color_from_hsv(h: f64, s: f64, v: f64) -> Color {
+    let s = s.clamp(0., 1.);
+    let v = v.clamp(0., 1.);
+    let chroma = v * s;
+    color_from_hue_chroma(h, chroma, v - chroma)
+}
+
+/// The part of the HSL and HSV conversions that they have in common.
+///
+/// `min` is the amount of each of red, green and blue that is added to the pure hue.
+fn color_from_hue_chroma(h: f64, chroma: f64, min: f64) -> Color {
+    let h = h.rem_euclid(360.) / 60.;
+    let x = chroma * (1. - (h.rem_euclid(2.) - 1.).abs());
+    let (r, g, b) = match h as u8 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    Color::rgb(r + min, g + min, b + min)
+}
+
+/// A simple way of writing out numbers, e.g. for the values in a chart legend.
+///
+/// The default writes numbers the same way as their `Display` impl (so `5.0` is written as `5`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// The number of digits to write after the decimal point, or `None` to use as many as needed.
+    pub decimals: Option<usize>,
+    /// A character to put between each group of 3 digits in the whole part (e.g. `1,234`), or
+    /// `None` to not group digits.
+    pub thousands_separator: Option<char>,
+    /// Text to write before the number (e.g. a currency symbol), after any minus sign.
+    pub prefix: String,
+    /// Text to write after the number (e.g. a unit).
+    pub suffix: String,
+}
+
+impl NumberFormat {
+    /// A format that writes numbers the same way as their `Display` impl.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always write the given number of digits after the decimal point.
+    pub fn with_decimals(mut self, decimals: usize) -> Self {
+        self.decimals = Some(decimals);
+        self
+    }
+
+    /// Put `separator` between each group of 3 digits in the whole part.
+    pub fn with_thousands_separator(mut self, separator: char) -> Self {
+        self.thousands_separator = Some(separator);
+        self
+    }
+
+    /// Write `prefix` before each number.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Write `suffix` after each number.
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    /// Write out `value` using this format.
+    pub fn format(&self, value: f64) -> String {
+        let digits = match self.decimals {
+            Some(decimals) => format!("{:.*}", decimals, value.abs()),
+            None => value.abs().to_string(),
+        };
+        let (whole, frac) = match digits.find('.') {
+            Some(idx) => digits.split_at(idx),
+            None => (digits.as_str(), ""),
+        };
+        // don't write `-0.00` for small negative numbers
+        let negative = value < 0. && digits.chars().any(|c| matches!(c, '1'..='9'));
+
+        let mut out = String::with_capacity(digits.len() + self.prefix.len() + self.suffix.len());
+        if negative {
+            out.push('-');
+        }
+        out.push_str(&self.prefix);
+        match self.thousands_separator {
+            Some(separator) if whole.bytes().all(|b| b.is_ascii_digit()) => {
+                for (idx, c) in whole.chars().enumerate() {
+                    if idx > 0 && (whole.len() - idx) % 3 == 0 {
+                        out.push(separator);
+                    }
+                    out.push(c);
+                }
+            }
+            _ => out.push_str(whole),
+        }
+        out.push_str(frac);
+        out.push_str(&self.suffix);
+        out
+    }
+}
+
+/// (De)serialize an optional color as a hex string (see [`color_to_hex`]).
+#[cfg(feature = "serde")]
+pub(crate) mod serde_color {
+    use super::{color_to_hex, parse_hex_color, Color};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Option<Color>, ser: S) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => ser.serialize_some(&color_to_hex(*color)),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Option<Color>, D::Error> {
+        match Option::<String>::deserialize(de)? {
+            Some(s) => parse_hex_color(&s).map(Some).map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Which side of some text a marker (e.g. a legend color swatch) should be drawn on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    /// Draw the marker before (to the left of) the text.
+    Before,
+    /// Draw the marker after (to the right of) the text.
+    After,
 }
 
 /// A tpye that knows how to select colors for different data in a chart.
+#[cfg(feature = "render")]
 pub trait ColorPalette: dyn_clone::DynClone {
     /// Given the index of the data point, select a base color to use.
     ///
@@ -67,14 +494,331 @@ pub trait ColorPalette: dyn_clone::DynClone {
     fn color(&self, index: usize) -> piet::Color;
 }
 
+#[cfg(feature = "render")]
 dyn_clone::clone_trait_object!(ColorPalette);
 
 /// A default color palette for choosing contrasting colors for a chart.
+#[cfg(feature = "render")]
 #[derive(Copy, Clone)]
 pub struct DefaultPalette;
+#[cfg(feature = "render")]
 impl ColorPalette for DefaultPalette {
     fn color(&self, index: usize) -> piet::Color {
         let hue = (index as f64 * 140.).rem_euclid(360.);
         piet::Color::hlc(hue, 40., 40.)
     }
 }
+
+/// A color palette that cycles through a fixed list of colors.
+#[cfg(feature = "render")]
+#[derive(Debug, Clone)]
+pub struct FixedPalette {
+    colors: Vec<Color>,
+}
+
+#[cfg(feature = "render")]
+impl FixedPalette {
+    /// Create a palette from a list of colors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `colors` is empty.
+    pub fn new(colors: Vec<Color>) -> Self {
+        assert!(!colors.is_empty(), "a palette needs at least one color");
+        Self { colors }
+    }
+
+    /// Create a palette from a list of hex color strings (e.g. `"#ff8800"`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `colors` is empty.
+    pub fn from_hex(colors: &[&str]) -> Result<Self, ColorParseError> {
+        let colors = colors
+            .iter()
+            .map(|hex| parse_hex_color(hex))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(colors))
+    }
+}
+
+#[cfg(feature = "render")]
+impl ColorPalette for FixedPalette {
+    fn color(&self, index: usize) -> piet::Color {
+        self.colors[index % self.colors.len()]
+    }
+}
+
+/// The Okabe-Ito palette of 8 colors, chosen to be distinguishable with common forms of color
+/// blindness.
+///
+/// Recommended for charts where accessibility matters. Colors repeat after the 8th data point.
+/// Black is moved from the start of the palette to the end, so that it's only used when there
+/// are lots of data points.
+#[cfg(feature = "render")]
+#[derive(Copy, Clone)]
+pub struct OkabeItoPalette;
+
+#[cfg(feature = "render")]
+impl OkabeItoPalette {
+    const COLORS: [Color; 8] = [
+        Color::rgb8(0xe6, 0x9f, 0x00), // orange
+        Color::rgb8(0x56, 0xb4, 0xe9), // sky blue
+        Color::rgb8(0x00, 0x9e, 0x73), // bluish green
+        Color::rgb8(0xf0, 0xe4, 0x42), // yellow
+        Color::rgb8(0x00, 0x72, 0xb2), // blue
+        Color::rgb8(0xd5, 0x5e, 0x00), // vermillion
+        Color::rgb8(0xcc, 0x79, 0xa7), // reddish purple
+        Color::rgb8(0x00, 0x00, 0x00), // black
+    ];
+}
+
+#[cfg(feature = "render")]
+impl ColorPalette for OkabeItoPalette {
+    fn color(&self, index: usize) -> piet::Color {
+        Self::COLORS[index % Self::COLORS.len()]
+    }
+}
+
+/// A hatching pattern drawn over a shape, so it can be told apart without relying on color (e.g.
+/// when printed in black and white).
+#[cfg(feature = "render")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// No pattern, just the fill color.
+    None,
+    /// Lines running from bottom left to top right (`/`).
+    DiagonalLines,
+    /// Lines running from top left to bottom right (`\`).
+    ReverseDiagonalLines,
+    /// Diagonal lines in both directions.
+    CrossHatch,
+    /// Horizontal lines.
+    HorizontalLines,
+    /// Vertical lines.
+    VerticalLines,
+    /// A grid of dots.
+    Dots,
+}
+
+/// The distance between the lines (or dots) of a [`Pattern`].
+#[cfg(feature = "render")]
+const HATCH_SPACING: f64 = 6.;
+/// The width of the lines of a [`Pattern`].
+#[cfg(feature = "render")]
+const HATCH_WIDTH: f64 = 1.;
+/// The radius of the dots of [`Pattern::Dots`].
+#[cfg(feature = "render")]
+const HATCH_DOT_RADIUS: f64 = 1.2;
+
+#[cfg(feature = "render")]
+impl Pattern {
+    /// Draw the pattern over `shape` using `brush`, clipped to the shape.
+    pub(crate) fn draw<RC: piet::RenderContext>(
+        self,
+        ctx: &mut RC,
+        shape: impl kurbo::Shape,
+        brush: &RC::Brush,
+    ) -> Result<(), piet::Error> {
+        use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+        // angles of the lines, where the y axis points down
+        let angles: &[f64] = match self {
+            Pattern::None => return Ok(()),
+            Pattern::DiagonalLines => &[-FRAC_PI_4],
+            Pattern::ReverseDiagonalLines => &[FRAC_PI_4],
+            Pattern::CrossHatch => &[-FRAC_PI_4, FRAC_PI_4],
+            Pattern::HorizontalLines => &[0.],
+            Pattern::VerticalLines => &[FRAC_PI_2],
+            Pattern::Dots => &[],
+        };
+        let bounds = shape.bounding_box();
+        ctx.with_save(|ctx| {
+            ctx.clip(shape);
+            for &angle in angles {
+                hatch_lines(ctx, bounds, angle, brush);
+            }
+            if self == Pattern::Dots {
+                // the grid is aligned to the origin, so neighbouring shapes line up
+                let mut y = (bounds.y0 / HATCH_SPACING).floor() * HATCH_SPACING;
+                while y <= bounds.y1 + HATCH_DOT_RADIUS {
+                    let mut x = (bounds.x0 / HATCH_SPACING).floor() * HATCH_SPACING;
+                    while x <= bounds.x1 + HATCH_DOT_RADIUS {
+                        let dot = kurbo::Circle::new((x, y), HATCH_DOT_RADIUS);
+                        ctx.fill(dot, brush);
+                        x += HATCH_SPACING;
+                    }
+                    y += HATCH_SPACING;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Draw parallel lines at the given angle covering all of `bounds`.
+#[cfg(feature = "render")]
+fn hatch_lines<RC: piet::RenderContext>(
+    ctx: &mut RC,
+    bounds: kurbo::Rect,
+    angle: f64,
+    brush: &RC::Brush,
+) {
+    let dir = kurbo::Vec2::from_angle(angle);
+    let normal = kurbo::Vec2::new(-dir.y, dir.x);
+    let center = bounds.center().to_vec2();
+    // long enough to cross the bounds whatever the angle
+    let half_len = 0.5 * bounds.size().to_vec2().hypot();
+    // lines are spaced from the origin, so neighbouring shapes line up
+    let first = ((center.dot(normal) - half_len) / HATCH_SPACING).floor() as i64;
+    let last = ((center.dot(normal) + half_len) / HATCH_SPACING).ceil() as i64;
+    let along = center.dot(dir);
+    for idx in first..=last {
+        let offset = normal * (idx as f64 * HATCH_SPACING);
+        let start = (offset + dir * (along - half_len)).to_point();
+        let end = (offset + dir * (along + half_len)).to_point();
+        ctx.stroke(kurbo::Line::new(start, end), brush, HATCH_WIDTH);
+    }
+}
+
+/// A type that knows how to select hatching patterns for different data in a chart.
+///
+/// This is used alongside a [`ColorPalette`], so data can be told apart by the pattern as well as
+/// the color.
+#[cfg(feature = "render")]
+pub trait PatternPalette: dyn_clone::DynClone {
+    /// Given the index of the data point, select a pattern to use.
+    ///
+    /// This function is expected to give the same answer for the same input (i.e. be a pure fn).
+    fn pattern(&self, index: usize) -> Pattern;
+}
+
+#[cfg(feature = "render")]
+dyn_clone::clone_trait_object!(PatternPalette);
+
+/// A palette cycling through a handful of distinct hatching patterns, for charts that will be
+/// printed in black and white.
+///
+/// Patterns repeat after the 6th data point. For charts without any color at all, use it with a
+/// [`FixedPalette`] containing only white.
+#[cfg(feature = "render")]
+#[derive(Copy, Clone)]
+pub struct HatchPalette;
+
+#[cfg(feature = "render")]
+impl HatchPalette {
+    const PATTERNS: [Pattern; 6] = [
+        Pattern::DiagonalLines,
+        Pattern::Dots,
+        Pattern::HorizontalLines,
+        Pattern::CrossHatch,
+        Pattern::ReverseDiagonalLines,
+        Pattern::VerticalLines,
+    ];
+}
+
+#[cfg(feature = "render")]
+impl PatternPalette for HatchPalette {
+    fn pattern(&self, index: usize) -> Pattern {
+        Self::PATTERNS[index % Self::PATTERNS.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_format_default() {
+        let format = NumberFormat::default();
+        for value in [0., 5., 5.5, -3.25, 1234567., 0.1 + 0.2] {
+            assert_eq!(format.format(value), value.to_string());
+        }
+    }
+
+    #[test]
+    fn number_format_decimals() {
+        let format = NumberFormat::new().with_decimals(2);
+        assert_eq!(format.format(5.), "5.00");
+        assert_eq!(format.format(2. / 3.), "0.67");
+        assert_eq!(format.format(-1.5), "-1.50");
+        // no negative zero
+        assert_eq!(format.format(-0.001), "0.00");
+        assert_eq!(NumberFormat::new().with_decimals(0).format(2.5), "2");
+    }
+
+    #[test]
+    fn number_format_thousands_separator() {
+        let format = NumberFormat::new().with_thousands_separator(',');
+        assert_eq!(format.format(123.), "123");
+        assert_eq!(format.format(1234.), "1,234");
+        assert_eq!(format.format(1234567.5), "1,234,567.5");
+        assert_eq!(format.format(-123456.), "-123,456");
+        let format = format.with_decimals(1);
+        assert_eq!(format.format(999999.99), "1,000,000.0");
+    }
+
+    #[test]
+    fn number_format_prefix_and_suffix() {
+        let format = NumberFormat::new()
+            .with_decimals(1)
+            .with_prefix("$")
+            .with_suffix(" kg");
+        assert_eq!(format.format(12.34), "$12.3 kg");
+        // the minus sign goes before the prefix
+        assert_eq!(format.format(-2.), "-$2.0 kg");
+        assert_eq!(NumberFormat::new().with_suffix("%").format(50.), "50%");
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn fixed_palette_cycles() {
+        let palette = FixedPalette::from_hex(&["#ff0000", "#00ff00", "#0000ff"]).unwrap();
+        assert_eq!(palette.color(1), Color::rgb8(0, 0xff, 0));
+        // the 5th color is the 2nd one again
+        assert_eq!(palette.color(4), palette.color(1));
+        assert_eq!(palette.color(3), palette.color(0));
+
+        assert!(FixedPalette::from_hex(&["#ff0000", "red"]).is_err());
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn fixed_palette_in_pie_style() {
+        fn is_shareable<T: Clone + Send + Sync>() {}
+        is_shareable::<FixedPalette>();
+
+        let palette = FixedPalette::new(vec![Color::BLACK, Color::WHITE]);
+        let style = crate::pie::PieStyle::default().with_segment_colors(palette);
+        assert_eq!(style.segment_colors.color(2), Color::BLACK);
+    }
+
+    #[test]
+    fn hex_colors() {
+        let white = Color::rgb8(0xff, 0xff, 0xff);
+        assert_eq!(parse_hex_color("#fff").unwrap(), white);
+        assert_eq!(parse_hex_color("#ffffff").unwrap(), white);
+        assert_eq!(
+            parse_hex_color("#ffffff80").unwrap(),
+            Color::rgba8(0xff, 0xff, 0xff, 0x80)
+        );
+        // the `#` is optional, and surrounding space is ignored
+        assert_eq!(parse_hex_color("ffffff").unwrap(), white);
+        assert_eq!(
+            parse_hex_color(" #f9f ").unwrap(),
+            Color::rgb8(0xff, 0x99, 0xff)
+        );
+
+        assert!(parse_hex_color("#ggg").is_err());
+        assert!(parse_hex_color("#fffff").is_err());
+        assert!(parse_hex_color("").is_err());
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        for color in [Color::rgb8(0x12, 0x34, 0x56), Color::rgba8(1, 2, 3, 4)] {
+            assert_eq!(parse_hex_color(&color_to_hex(color)).unwrap(), color);
+        }
+        assert_eq!(color_to_hex(Color::WHITE), "#ffffff");
+    }
+}
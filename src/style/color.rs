@@ -0,0 +1,135 @@
+//! A stand-in for `piet::Color`, used when the `render` feature is off so that parsed charts can
+//! still hold colors without depending on piet.
+//!
+//! Only the parts of piet's API that make sense without a renderer are provided, with the same
+//! names and behaviour, so code written against this type also works with piet's.
+
+use std::fmt;
+
+/// A 32 bit RGBA color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color(u32);
+
+/// Errors that can occur when parsing a hex color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorParseError {
+    /// The input string has an incorrect length.
+    WrongSize(usize),
+    /// A byte in the input string is not in one of the ranges `0..=9`, `a..=f`, or `A..=F`.
+    #[allow(missing_docs)]
+    NotHex { idx: usize, byte: u8 },
+}
+
+impl Color {
+    /// Opaque black.
+    pub const BLACK: Color = Color::rgb8(0, 0, 0);
+    /// Opaque white.
+    pub const WHITE: Color = Color::rgb8(255, 255, 255);
+    /// Fully transparent.
+    pub const TRANSPARENT: Color = Color::rgba8(0, 0, 0, 0);
+
+    /// Create a color from 8 bit per sample RGB values.
+    pub const fn rgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::rgba8(r, g, b, 0xff)
+    }
+
+    /// Create a color from 8 bit per sample RGBA values.
+    pub const fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::from_rgba32_u32(
+            ((r as u32) << 24) | ((g as u32) << 16) | ((b as u32) << 8) | a as u32,
+        )
+    }
+
+    /// Create a color from a 32-bit rgba value (alpha as least significant byte).
+    pub const fn from_rgba32_u32(rgba: u32) -> Color {
+        Color(rgba)
+    }
+
+    /// Create a color from four floating point values, each in the range `0.0` to `1.0`.
+    pub fn rgba(r: f64, g: f64, b: f64, a: f64) -> Color {
+        let channel = |v: f64| (v.clamp(0., 1.) * 255.).round() as u8;
+        Color::rgba8(channel(r), channel(g), channel(b), channel(a))
+    }
+
+    /// Create a color from three floating point values, each in the range `0.0` to `1.0`.
+    pub fn rgb(r: f64, g: f64, b: f64) -> Color {
+        Color::rgba(r, g, b, 1.)
+    }
+
+    /// Parse a color from a hex string like `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    ///
+    /// The leading `#` is optional.
+    pub fn from_hex_str(hex: &str) -> Result<Color, ColorParseError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex).as_bytes();
+        // expand to 8 digits, with opaque alpha if there isn't any
+        let digits = match *digits {
+            [r, g, b] => [r, r, g, g, b, b, b'f', b'f'],
+            [r, g, b, a] => [r, r, g, g, b, b, a, a],
+            [r0, r1, g0, g1, b0, b1] => [r0, r1, g0, g1, b0, b1, b'f', b'f'],
+            [r0, r1, g0, g1, b0, b1, a0, a1] => [r0, r1, g0, g1, b0, b1, a0, a1],
+            ref other => return Err(ColorParseError::WrongSize(other.len())),
+        };
+        let mut rgba = 0;
+        for (idx, byte) in digits.into_iter().enumerate() {
+            let digit = (byte as char)
+                .to_digit(16)
+                .ok_or(ColorParseError::NotHex { idx, byte })?;
+            rgba = rgba << 4 | digit;
+        }
+        Ok(Color(rgba))
+    }
+
+    /// Convert a color value to a 32-bit rgba value.
+    pub const fn as_rgba_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Convert a color value to four 8-bit rgba values.
+    pub fn as_rgba8(self) -> (u8, u8, u8, u8) {
+        let [r, g, b, a] = self.0.to_be_bytes();
+        (r, g, b, a)
+    }
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorParseError::WrongSize(len) => write!(f, "input string has invalid length {}", len),
+            ColorParseError::NotHex { idx, byte } => {
+                write!(f, "byte {:X} at index {} is not valid hex digit", byte, idx)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_hex_str() {
+        assert_eq!(Color::from_hex_str("#BAD"), Ok(Color(0xbbaaddff)));
+        assert_eq!(Color::from_hex_str("bad"), Color::from_hex_str("#bbaadd"));
+        assert_eq!(Color::from_hex_str("#bad8"), Ok(Color(0xbbaadd88)));
+        assert_eq!(
+            Color::from_hex_str("#12345678").unwrap().as_rgba8(),
+            (0x12, 0x34, 0x56, 0x78)
+        );
+        assert_eq!(
+            Color::from_hex_str("#12345"),
+            Err(ColorParseError::WrongSize(5))
+        );
+        assert_eq!(
+            Color::from_hex_str("#12g"),
+            Err(ColorParseError::NotHex { idx: 4, byte: b'g' })
+        );
+    }
+
+    #[test]
+    fn float_channels() {
+        assert_eq!(Color::rgb(1., 0.5, -1.), Color::rgb8(255, 128, 0));
+        assert_eq!(Color::rgba(0., 0., 0., 0.), Color::TRANSPARENT);
+    }
+}
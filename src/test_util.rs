@@ -0,0 +1,332 @@
+//! Helpers for testing code that draws charts.
+
+use kurbo::{Affine, BezPath, Point, Rect, Shape, Size};
+use piet::{
+    Color, Error, FixedGradient, FontFamily, HitTestPoint, HitTestPosition, ImageFormat,
+    InterpolationMode, IntoBrush, LineMetric, NullImage, RenderContext, Text, TextAlignment,
+    TextAttribute, TextLayout, TextLayoutBuilder, TextStorage,
+};
+use std::{borrow::Cow, ops::RangeBounds};
+
+/// Something drawn to a [`Recorder`].
+///
+/// Shapes and positions have the render context's transform applied.
+#[derive(Debug, Clone)]
+pub(crate) enum Op {
+    Fill {
+        shape: BezPath,
+        color: Color,
+    },
+    Stroke {
+        shape: BezPath,
+        color: Color,
+        width: f64,
+    },
+    /// Some text was drawn, taking up `rect`.
+    Text {
+        text: String,
+        rect: Rect,
+    },
+}
+
+/// A render context that records what is drawn to it, rather than drawing it.
+///
+/// Text is laid out using [`FixedText`], which takes up no space unless the recorder is made
+/// with [`Recorder::with_char_size`].
+pub(crate) struct Recorder {
+    pub ops: Vec<Op>,
+    text: FixedText,
+    transform: Affine,
+    saved: Vec<Affine>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::with_char_size(0.)
+    }
+
+    /// A recorder where each character of text is a `char_size` square.
+    pub fn with_char_size(char_size: f64) -> Self {
+        Recorder {
+            ops: vec![],
+            text: FixedText { char_size },
+            transform: Affine::IDENTITY,
+            saved: vec![],
+        }
+    }
+
+    /// The shape and color of everything that was filled.
+    pub fn fills(&self) -> Vec<(&BezPath, Color)> {
+        self.ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Fill { shape, color } => Some((shape, *color)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The shape, color and width of everything that was stroked.
+    pub fn strokes(&self) -> Vec<(&BezPath, Color, f64)> {
+        self.ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Stroke {
+                    shape,
+                    color,
+                    width,
+                } => Some((shape, *color, *width)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Where each piece of text was drawn.
+    pub fn texts(&self) -> Vec<Point> {
+        self.text_rects()
+            .into_iter()
+            .map(|(_, rect)| rect.origin())
+            .collect()
+    }
+
+    /// Each piece of text that was drawn, and the area it takes up.
+    pub fn text_rects(&self) -> Vec<(&str, Rect)> {
+        self.ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Text { text, rect } => Some((text.as_str(), *rect)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn path(&self, shape: impl Shape) -> BezPath {
+        self.transform * shape.into_path(0.1)
+    }
+
+    fn color(&mut self, shape: &impl Shape, brush: &impl IntoBrush<Self>) -> Color {
+        brush.make_brush(self, || shape.bounding_box()).0
+    }
+}
+
+/// A brush for a [`Recorder`], which is always a solid color.
+#[derive(Clone)]
+pub(crate) struct Brush(Color);
+
+impl IntoBrush<Recorder> for Brush {
+    fn make_brush<'a>(&'a self, _: &mut Recorder, _: impl FnOnce() -> Rect) -> Cow<'a, Brush> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl RenderContext for Recorder {
+    type Brush = Brush;
+    type Text = FixedText;
+    type TextLayout = FixedLayout;
+    type Image = NullImage;
+
+    fn status(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn solid_brush(&mut self, color: Color) -> Brush {
+        Brush(color)
+    }
+
+    fn gradient(&mut self, _: impl Into<FixedGradient>) -> Result<Brush, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn clear(&mut self, _: impl Into<Option<Rect>>, _: Color) {}
+
+    fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
+        self.stroke_styled(shape, brush, width, &piet::StrokeStyle::new());
+    }
+
+    fn stroke_styled(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        _: &piet::StrokeStyle,
+    ) {
+        let color = self.color(&shape, brush);
+        let shape = self.path(shape);
+        self.ops.push(Op::Stroke {
+            shape,
+            color,
+            width,
+        });
+    }
+
+    fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let color = self.color(&shape, brush);
+        let shape = self.path(shape);
+        self.ops.push(Op::Fill { shape, color });
+    }
+
+    fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        self.fill(shape, brush);
+    }
+
+    fn clip(&mut self, _: impl Shape) {}
+
+    fn text(&mut self) -> &mut FixedText {
+        &mut self.text
+    }
+
+    fn draw_text(&mut self, layout: &FixedLayout, pos: impl Into<Point>) {
+        let rect = Rect::from_origin_size(pos.into(), layout.size);
+        self.ops.push(Op::Text {
+            text: layout.text.clone(),
+            rect: self.transform.transform_rect_bbox(rect),
+        });
+    }
+
+    fn save(&mut self) -> Result<(), Error> {
+        self.saved.push(self.transform);
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<(), Error> {
+        self.transform = self.saved.pop().ok_or(Error::StackUnbalance)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.transform *= transform;
+    }
+
+    fn make_image_with_stride(
+        &mut self,
+        _: usize,
+        _: usize,
+        _: usize,
+        _: &[u8],
+        _: ImageFormat,
+    ) -> Result<NullImage, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn capture_image_area(&mut self, _: impl Into<Rect>) -> Result<NullImage, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn draw_image(&mut self, _: &NullImage, _: impl Into<Rect>, _: InterpolationMode) {}
+
+    fn draw_image_area(
+        &mut self,
+        _: &NullImage,
+        _: impl Into<Rect>,
+        _: impl Into<Rect>,
+        _: InterpolationMode,
+    ) {
+    }
+
+    fn blurred_rect(&mut self, _: Rect, _: f64, _: &impl IntoBrush<Self>) {}
+
+    fn current_transform(&self) -> Affine {
+        self.transform
+    }
+}
+
+/// Text where every character is the same size, ignoring any styling.
+#[derive(Clone, Default)]
+pub(crate) struct FixedText {
+    /// The width and height of each character.
+    pub char_size: f64,
+}
+
+/// Laid out [`FixedText`].
+#[derive(Clone)]
+pub(crate) struct FixedLayout {
+    text: String,
+    size: Size,
+}
+
+impl Text for FixedText {
+    type TextLayoutBuilder = FixedLayout;
+    type TextLayout = FixedLayout;
+
+    fn font_family(&mut self, _: &str) -> Option<FontFamily> {
+        Some(FontFamily::default())
+    }
+
+    fn load_font(&mut self, _: &[u8]) -> Result<FontFamily, Error> {
+        Ok(FontFamily::default())
+    }
+
+    fn new_text_layout(&mut self, text: impl TextStorage) -> FixedLayout {
+        let text = text.as_str().to_string();
+        let lines = text.split('\n');
+        let width = lines.clone().map(|line| line.chars().count()).max();
+        let size = Size::new(width.unwrap_or(0) as f64, lines.count() as f64) * self.char_size;
+        FixedLayout { text, size }
+    }
+}
+
+impl TextLayoutBuilder for FixedLayout {
+    type Out = FixedLayout;
+
+    fn max_width(self, _: f64) -> Self {
+        self
+    }
+
+    fn alignment(self, _: TextAlignment) -> Self {
+        self
+    }
+
+    fn default_attribute(self, _: impl Into<TextAttribute>) -> Self {
+        self
+    }
+
+    fn range_attribute(self, _: impl RangeBounds<usize>, _: impl Into<TextAttribute>) -> Self {
+        self
+    }
+
+    fn build(self) -> Result<FixedLayout, Error> {
+        Ok(self)
+    }
+}
+
+impl TextLayout for FixedLayout {
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    fn trailing_whitespace_width(&self) -> f64 {
+        self.size.width
+    }
+
+    fn image_bounds(&self) -> Rect {
+        self.size.to_rect()
+    }
+
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        self.text.split('\n').nth(line_number)
+    }
+
+    fn line_metric(&self, _: usize) -> Option<LineMetric> {
+        None
+    }
+
+    fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+
+    fn hit_test_point(&self, _: Point) -> HitTestPoint {
+        HitTestPoint::default()
+    }
+
+    fn hit_test_text_position(&self, _: usize) -> HitTestPosition {
+        HitTestPosition::default()
+    }
+}
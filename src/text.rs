@@ -0,0 +1,247 @@
+//! Helpers for working with the text of labels.
+//!
+//! Labels are kept exactly as they were written in the source, so they can be written back out
+//! unchanged. Use [`unescape`] to get the text that should be displayed.
+
+#[cfg(feature = "render")]
+use once_cell::sync::Lazy;
+#[cfg(feature = "render")]
+use regex::Regex;
+use std::borrow::Cow;
+
+/// The entity mermaid uses for a `"` inside a quoted label.
+pub(crate) const QUOT_ENTITY: &str = "#quot;";
+
+/// Get the text a label from the source stands for.
+///
+/// Inside quoted labels a `"` can be written as `\"` or `#quot;`, and a backslash as `\\`. Other
+/// backslashes are left alone, so line breaks written as `\n` are kept.
+pub fn unescape(label: &str) -> Cow<'_, str> {
+    if !label.contains('\\') && !label.contains(QUOT_ENTITY) {
+        return Cow::Borrowed(label);
+    }
+    let mut out = String::with_capacity(label.len());
+    let mut chars = label.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('\\', Some(&next @ ('"' | '\\'))) => {
+                out.push(next);
+                chars.next();
+            }
+            (ch, _) => out.push(ch),
+        }
+    }
+    Cow::Owned(out.replace(QUOT_ENTITY, "\""))
+}
+
+/// Matches the ways of writing a line break in a label: `<br>`, `<br/>` or a literal `\n`.
+#[cfg(feature = "render")]
+static LINE_BREAK: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)<br\s*/?>|\\n").unwrap());
+
+/// Get the text to draw for a label: escapes are removed (see [`unescape`]) and line breaks are
+/// replaced with newlines, which piet lays out as separate lines.
+#[cfg(feature = "render")]
+pub(crate) fn label_text(label: &str) -> Cow<'_, str> {
+    match unescape(label) {
+        Cow::Borrowed(label) => LINE_BREAK.replace_all(label, "\n"),
+        Cow::Owned(label) => Cow::Owned(LINE_BREAK.replace_all(&label, "\n").into_owned()),
+    }
+}
+
+/// Escape a label so it can be written inside double quotes.
+///
+/// Any `"` that would end the string is written as `#quot;`, and a backslash at the very end is
+/// doubled so it doesn't escape the closing quote. Labels parsed from quoted strings don't change.
+pub(crate) fn escape_quotes(label: &str) -> Cow<'_, str> {
+    if closing_quote(label).is_none() && !label.ends_with('\\') {
+        return Cow::Borrowed(label);
+    }
+    let mut out = String::with_capacity(label.len());
+    let mut escaped = false;
+    for ch in label.chars() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => {
+                out.push_str(QUOT_ENTITY);
+                continue;
+            }
+            _ => (),
+        }
+        out.push(ch);
+    }
+    if escaped {
+        out.push('\\');
+    }
+    Cow::Owned(out)
+}
+
+/// Find the quote that ends a quoted string, given the text after the opening quote.
+///
+/// A backslash escapes the character after it, so `\"` doesn't end the string but `\\"` does.
+/// Returns `None` if the string isn't closed.
+pub(crate) fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(idx),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Text made up of runs with their own formatting, from a markdown string label.
+///
+/// Mermaid treats a label wrapped in backticks (e.g. ``A["`**bold** text`"]``) as markdown. Only
+/// `**bold**` and `*italic*` are supported so far.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RichText {
+    /// The runs of text, in order.
+    pub runs: Vec<TextRun>,
+}
+
+/// Part of a [`RichText`] where all the text has the same formatting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextRun {
+    /// The text of the run.
+    pub text: String,
+    /// Whether the text is bold.
+    pub bold: bool,
+    /// Whether the text is italic.
+    pub italic: bool,
+}
+
+impl RichText {
+    /// Get the text to display for a label, which should already have been unescaped.
+    ///
+    /// Labels wrapped in backticks are parsed as markdown (see [`RichText::parse_markdown`]), and
+    /// any other label is a single plain run.
+    pub fn from_label(label: &str) -> Self {
+        match label
+            .strip_prefix('`')
+            .and_then(|label| label.strip_suffix('`'))
+        {
+            Some(markdown) => Self::parse_markdown(markdown),
+            None => Self::plain(label),
+        }
+    }
+
+    /// A single run of unformatted text.
+    pub fn plain(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let runs = if text.is_empty() {
+            vec![]
+        } else {
+            vec![TextRun {
+                text,
+                bold: false,
+                italic: false,
+            }]
+        };
+        RichText { runs }
+    }
+
+    /// Parse `**bold**` and `*italic*` text.
+    ///
+    /// Markers are paired up in order, and one without a partner is kept as text.
+    pub fn parse_markdown(text: &str) -> Self {
+        let mut tokens = vec![];
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (token, len) = if rest.starts_with("**") {
+                (Token::Marker(2), 2)
+            } else if rest.starts_with('*') {
+                (Token::Marker(1), 1)
+            } else {
+                let len = rest.find('*').unwrap_or(rest.len());
+                (Token::Text(&rest[..len]), len)
+            };
+            tokens.push(token);
+            rest = &rest[len..];
+        }
+
+        // pair up the markers of each kind, so unpaired ones can be treated as text
+        let mut paired = vec![false; tokens.len()];
+        for kind in [1, 2] {
+            let markers = (0..tokens.len())
+                .filter(|&idx| tokens[idx] == Token::Marker(kind))
+                .collect::<Vec<_>>();
+            for pair in markers.chunks_exact(2) {
+                paired[pair[0]] = true;
+                paired[pair[1]] = true;
+            }
+        }
+
+        let mut out = RichText::default();
+        let (mut bold, mut italic) = (false, false);
+        for (token, paired) in tokens.into_iter().zip(paired) {
+            match token {
+                Token::Marker(2) if paired => bold = !bold,
+                Token::Marker(_) if paired => italic = !italic,
+                Token::Marker(len) => out.push(&"**"[..len], bold, italic),
+                Token::Text(text) => out.push(text, bold, italic),
+            }
+        }
+        out
+    }
+
+    /// Add text to the end, joining it to the last run if the formatting is the same.
+    fn push(&mut self, text: &str, bold: bool, italic: bool) {
+        match self.runs.last_mut() {
+            Some(last) if last.bold == bold && last.italic == italic => last.text.push_str(text),
+            _ => self.runs.push(TextRun {
+                text: text.to_string(),
+                bold,
+                italic,
+            }),
+        }
+    }
+
+    /// Get the text without any formatting.
+    pub fn plain_text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+
+    /// Whether none of the text is formatted.
+    pub fn is_plain(&self) -> bool {
+        self.runs.iter().all(|run| !run.bold && !run.italic)
+    }
+}
+
+/// Part of a markdown string, see [`RichText::parse_markdown`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    /// Some text without any markers in it.
+    Text(&'a str),
+    /// A run of 1 (italic) or 2 (bold) `*`s.
+    Marker(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_quotes_round_trip() {
+        for label in [
+            "plain",
+            "say \"hi\"",
+            "\\\"kept\\\"",
+            "#quot;",
+            "ends with \\",
+        ] {
+            let escaped = escape_quotes(label);
+            // the escaped label is a whole quoted string
+            assert_eq!(
+                closing_quote(&format!("{}\"", escaped)),
+                Some(escaped.len())
+            );
+            assert_eq!(unescape(&escaped), unescape(label));
+        }
+        assert_eq!(escape_quotes("say \"hi\""), "say #quot;hi#quot;");
+        assert_eq!(escape_quotes("\\\"kept\\\""), "\\\"kept\\\"");
+    }
+}